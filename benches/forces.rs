@@ -0,0 +1,59 @@
+use atomica::{
+    boundary::PeriodicBox,
+    particle::{Particle, ParticleStore},
+    potential::{ForceField, LennardJones},
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{Rng, SeedableRng};
+
+/// A square lattice of `count` particles a little wider apart than the Lennard-Jones minimum,
+/// nudged off their sites and wrapped in a box that fits them exactly.
+fn lattice(count: usize) -> (ParticleStore, PeriodicBox) {
+    let side = (count as f64).sqrt().ceil() as usize;
+    let spacing = 1.15;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    let particles = (0..count)
+        .map(|i| {
+            Particle::new(
+                cgmath::point2(
+                    (i % side) as f64 * spacing + rng.gen_range(-0.1..0.1),
+                    (i / side) as f64 * spacing + rng.gen_range(-0.1..0.1),
+                ),
+                cgmath::vec2(0.0, 0.0),
+                1.0,
+                0.0,
+            )
+        })
+        .collect();
+    let size = side as f64 * spacing;
+    let periodic_box = PeriodicBox {
+        min: cgmath::point2(0.0, 0.0),
+        size: cgmath::vec2(size, size),
+    };
+    (particles, periodic_box)
+}
+
+fn forces(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lennard-jones forces");
+    group.sample_size(10);
+    for count in [1_000, 10_000, 100_000] {
+        let (particles, periodic_box) = lattice(count);
+        for parallel in [false, true] {
+            let mut field = ForceField {
+                periodic_box: Some(periodic_box),
+                parallel,
+                ..ForceField::new(vec![Box::new(LennardJones::default())])
+            };
+            // builds the neighbour list, which then stays valid since nothing moves
+            field.forces(&particles);
+            let name = if parallel { "parallel" } else { "serial" };
+            group.bench_with_input(BenchmarkId::new(name, count), &particles, |b, particles| {
+                b.iter(|| field.forces(particles))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, forces);
+criterion_main!(benches);
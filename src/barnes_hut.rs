@@ -0,0 +1,311 @@
+use cgmath::{Array, EuclideanSpace};
+
+use crate::dimension::Vector;
+
+const LEAF_CAPACITY: usize = 8;
+const MAX_DEPTH: u32 = 32;
+
+enum NodeKind {
+    Leaf(Vec<usize>),
+    /// One child for every corner, 4 in 2D and 8 in 3D.
+    Branch(Vec<usize>),
+}
+
+struct Node<V: Vector> {
+    center: V::Point,
+    half_size: f64,
+    /// Total strength of every body inside the node.
+    monopole: f64,
+    /// Sum of strength * (position - center). Expanding around the geometric center rather
+    /// than the center of charge keeps this well defined for nodes that are close to neutral.
+    dipole: V,
+    kind: NodeKind,
+}
+
+/// Either a single body that has to be summed directly, or a node far enough away to be
+/// treated as a multipole. The vector points from the source to the evaluation point.
+enum Term<'a, V: Vector> {
+    Body(V, f64),
+    Node(V, &'a Node<V>),
+}
+
+impl<V: Vector> Node<V> {
+    fn contains(&self, p: V::Point) -> bool {
+        (0..V::DIMENSION).all(|i| (p[i] - self.center[i]).abs() <= self.half_size)
+    }
+}
+
+/// Quadtree, or octree in 3D, over point sources of either sign (charges, or masses with a
+/// negative coupling constant), approximating far away nodes by their monopole and dipole
+/// moments.
+pub struct Tree<V: Vector = cgmath::Vector2<f64>> {
+    bodies: Vec<(V::Point, f64)>,
+    nodes: Vec<Node<V>>,
+    /// Opening angle, nodes whose size over distance is below this are not opened.
+    theta: f64,
+}
+
+impl<V: Vector> Tree<V> {
+    pub fn new(bodies: Vec<(V::Point, f64)>, theta: f64) -> Self {
+        let mut min = V::Point::from_value(f64::INFINITY);
+        let mut max = V::Point::from_value(f64::NEG_INFINITY);
+        for (p, _) in &bodies {
+            for i in 0..V::DIMENSION {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+        let mut tree = Self {
+            bodies,
+            nodes: vec![],
+            theta,
+        };
+        if !tree.bodies.is_empty() {
+            let extent = max - min;
+            let widest = (0..V::DIMENSION).map(|i| extent[i]).fold(0.0, f64::max);
+            let half_size = (widest / 2.0).max(1e-9) * 1.0001;
+            let center = min.midpoint(max);
+            tree.build((0..tree.bodies.len()).collect(), center, half_size, 0);
+        }
+        tree
+    }
+
+    fn build(
+        &mut self,
+        indices: Vec<usize>,
+        center: V::Point,
+        half_size: f64,
+        depth: u32,
+    ) -> usize {
+        if indices.len() <= LEAF_CAPACITY || depth >= MAX_DEPTH {
+            let mut monopole = 0.0;
+            let mut dipole = V::zero();
+            for &i in &indices {
+                let (p, q) = self.bodies[i];
+                monopole += q;
+                dipole += (p - center) * q;
+            }
+            self.nodes.push(Node {
+                center,
+                half_size,
+                monopole,
+                dipole,
+                kind: NodeKind::Leaf(indices),
+            });
+            return self.nodes.len() - 1;
+        }
+
+        // bit i of a corner's index is set when it is on the positive side along axis i
+        let mut corners = vec![vec![]; 1 << V::DIMENSION];
+        for i in indices {
+            let p = self.bodies[i].0;
+            let corner = (0..V::DIMENSION)
+                .filter(|&axis| p[axis] >= center[axis])
+                .map(|axis| 1 << axis)
+                .sum::<usize>();
+            corners[corner].push(i);
+        }
+        let quarter = half_size / 2.0;
+        let mut children = Vec::with_capacity(corners.len());
+        for (corner, indices) in corners.iter_mut().enumerate() {
+            let offset = V::from_fn(|axis| {
+                if corner & 1 << axis != 0 {
+                    quarter
+                } else {
+                    -quarter
+                }
+            });
+            children.push(self.build(std::mem::take(indices), center + offset, quarter, depth + 1));
+        }
+
+        let mut monopole = 0.0;
+        let mut dipole = V::zero();
+        for &child in &children {
+            let child = &self.nodes[child];
+            monopole += child.monopole;
+            dipole += child.dipole + (child.center - center) * child.monopole;
+        }
+        self.nodes.push(Node {
+            center,
+            half_size,
+            monopole,
+            dipole,
+            kind: NodeKind::Branch(children),
+        });
+        self.nodes.len() - 1
+    }
+
+    fn root(&self) -> Option<usize> {
+        self.nodes.len().checked_sub(1)
+    }
+
+    fn walk(&self, at: V::Point, skip: Option<usize>, mut visit: impl FnMut(Term<V>)) {
+        let mut stack: Vec<usize> = self.root().into_iter().collect();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            match &node.kind {
+                NodeKind::Leaf(indices) => {
+                    for &i in indices {
+                        if Some(i) != skip {
+                            let (p, q) = self.bodies[i];
+                            visit(Term::Body(at - p, q));
+                        }
+                    }
+                }
+                NodeKind::Branch(children) => {
+                    let r = at - node.center;
+                    let size = 2.0 * node.half_size;
+                    if !node.contains(at) && size * size < self.theta * self.theta * r.magnitude2()
+                    {
+                        visit(Term::Node(r, node));
+                    } else {
+                        stack.extend(children.iter());
+                    }
+                }
+            }
+        }
+    }
+
+    /// The softened inverse-square field at `at` from every body except `skip`, i.e. the sum of
+    /// `q * r / (r^2 + softening^2)^(3/2)`.
+    pub fn field(&self, at: V::Point, softening: f64, skip: Option<usize>) -> V {
+        let eps2 = softening * softening;
+        let mut field = V::zero();
+        self.walk(at, skip, |term| match term {
+            Term::Body(r, q) => {
+                let d2 = r.magnitude2() + eps2;
+                field += r * (q / (d2 * d2.sqrt()));
+            }
+            Term::Node(r, node) => {
+                let d2 = r.magnitude2() + eps2;
+                let inv3 = 1.0 / (d2 * d2.sqrt());
+                field += r * (node.monopole * inv3);
+                field += (r * (3.0 * node.dipole.dot(r) / d2) - node.dipole) * inv3;
+            }
+        });
+        field
+    }
+
+    /// The softened `q / r` potential at `at` from every body except `skip`.
+    pub fn potential(&self, at: V::Point, softening: f64, skip: Option<usize>) -> f64 {
+        let eps2 = softening * softening;
+        let mut potential = 0.0;
+        self.walk(at, skip, |term| match term {
+            Term::Body(r, q) => potential += q / (r.magnitude2() + eps2).sqrt(),
+            Term::Node(r, node) => {
+                let d2 = r.magnitude2() + eps2;
+                potential += node.monopole / d2.sqrt() + node.dipole.dot(r) / (d2 * d2.sqrt());
+            }
+        });
+        potential
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    fn random_bodies(count: usize) -> Vec<(cgmath::Point2<f64>, f64)> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        (0..count)
+            .map(|i| {
+                let p = cgmath::point2(next() * 20.0 - 10.0, next() * 20.0 - 10.0);
+                (p, if i % 2 == 0 { 1.0 } else { -1.0 } * (0.5 + next()))
+            })
+            .collect()
+    }
+
+    fn direct_field(
+        bodies: &[(cgmath::Point2<f64>, f64)],
+        index: usize,
+        softening: f64,
+    ) -> cgmath::Vector2<f64> {
+        let at = bodies[index].0;
+        bodies
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != index)
+            .map(|(_, &(p, q))| {
+                let r = at - p;
+                let d2 = r.magnitude2() + softening * softening;
+                r * (q / (d2 * d2.sqrt()))
+            })
+            .sum()
+    }
+
+    fn relative_rms_error(theta: f64) -> f64 {
+        let bodies = random_bodies(1000);
+        let tree: Tree = Tree::new(bodies.clone(), theta);
+        let mut error = 0.0;
+        let mut total = 0.0;
+        for i in 0..bodies.len() {
+            let exact = direct_field(&bodies, i, 0.01);
+            error += (tree.field(bodies[i].0, 0.01, Some(i)) - exact).magnitude2();
+            total += exact.magnitude2();
+        }
+        (error / total).sqrt()
+    }
+
+    #[test]
+    fn zero_opening_angle_is_exact() {
+        assert!(relative_rms_error(0.0) < 1e-12);
+    }
+
+    #[test]
+    fn matches_direct_summation() {
+        let error = relative_rms_error(0.5);
+        assert!(error < 1e-2, "relative error {}", error);
+        assert!(relative_rms_error(0.3) < error);
+    }
+
+    #[test]
+    fn neutral_cluster_is_seen_through_its_dipole() {
+        let bodies: Vec<_> = (0..20)
+            .map(|i| {
+                let angle = i as f64 * 0.7;
+                let p = cgmath::point2(angle.cos() * 0.1 + 0.05, angle.sin() * 0.1);
+                (p, if i % 2 == 0 { 1.0 } else { -1.0 })
+            })
+            .collect();
+        // the cluster has no net charge, so from far away only the dipole term contributes
+        let at = cgmath::point2(50.0, 30.0);
+        let tree: Tree = Tree::new(bodies.clone(), 0.5);
+        let exact: cgmath::Vector2<f64> = bodies
+            .iter()
+            .map(|&(p, q)| (at - p) * (q / (at - p).magnitude().powi(3)))
+            .sum();
+        let approximate = tree.field(at, 0.0, None);
+        assert!((approximate - exact).magnitude() < 1e-2 * exact.magnitude());
+    }
+
+    #[test]
+    fn octree_matches_direct_summation_in_3d() {
+        let bodies: Vec<(cgmath::Point3<f64>, f64)> = random_bodies(600)
+            .chunks(2)
+            .map(|pair| {
+                let (a, b) = (pair[0].0, pair[1].0);
+                (cgmath::point3(a.x, a.y, b.x), pair[0].1)
+            })
+            .collect();
+        let tree: Tree<cgmath::Vector3<f64>> = Tree::new(bodies.clone(), 0.4);
+        let (mut error, mut total) = (0.0, 0.0);
+        for (i, &(at, _)) in bodies.iter().enumerate() {
+            let exact: cgmath::Vector3<f64> = bodies
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, &(p, q))| (at - p) * (q / (at - p).magnitude().powi(3)))
+                .sum();
+            error += (tree.field(at, 0.0, Some(i)) - exact).magnitude2();
+            total += exact.magnitude2();
+        }
+        assert!((error / total).sqrt() < 1e-2);
+    }
+}
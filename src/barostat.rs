@@ -0,0 +1,176 @@
+use crate::{boundary::PeriodicBox, dimension::Vector, particle::ParticleStore};
+
+/// Virial pressure in a box of `volume`, which is an area in 2D, with Boltzmann's constant 1.
+/// The ideal gas part `N k T` is `2 / dimension` of the kinetic energy.
+pub fn virial_pressure(kinetic_energy: f64, virial: f64, volume: f64, dimension: usize) -> f64 {
+    (2.0 * kinetic_energy + virial) / (dimension as f64 * volume)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarostatKind {
+    /// Rescales the box so the pressure relaxes exponentially towards the target. Doesn't
+    /// sample the right volume fluctuations, but is very stable for equilibrating.
+    Berendsen,
+    /// Martyna-Tobias-Klein: the box gets a momentum driven by the pressure error and a mass
+    /// set by the coupling time, and the particle velocities feel the expansion rate.
+    MartynaTobiasKlein,
+}
+
+impl BarostatKind {
+    pub const ALL: [BarostatKind; 2] = [BarostatKind::Berendsen, BarostatKind::MartynaTobiasKlein];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BarostatKind::Berendsen => "berendsen",
+            BarostatKind::MartynaTobiasKlein => "mtk",
+        }
+    }
+}
+
+/// Scales the periodic box, and every particle with it, towards a target pressure after every
+/// step. The box keeps its aspect ratio and centre.
+#[derive(Debug, Clone)]
+pub struct Barostat {
+    pub kind: BarostatKind,
+    pub target: f64,
+    pub coupling_time: f64,
+    /// How much the volume, or area in 2D, responds to pressure, `-dV / (V dP)`. Only used by
+    /// Berendsen.
+    pub compressibility: f64,
+    /// Logarithmic expansion rate of the box sides, the MTK box momentum over its mass.
+    expansion_rate: f64,
+}
+
+impl Barostat {
+    pub fn new(kind: BarostatKind, target: f64, coupling_time: f64) -> Self {
+        Self {
+            kind,
+            target,
+            coupling_time,
+            compressibility: 1.0,
+            expansion_rate: 0.0,
+        }
+    }
+
+    pub fn apply<V: Vector>(
+        &mut self,
+        particles: &mut ParticleStore<V>,
+        periodic_box: &mut PeriodicBox<V>,
+        pressure: f64,
+        dt: f64,
+    ) {
+        if particles.is_empty() {
+            return;
+        }
+        let d = V::DIMENSION as f64;
+        let (scale, velocity_scale) = match self.kind {
+            BarostatKind::Berendsen => {
+                let volume_scale =
+                    1.0 - self.compressibility * dt / self.coupling_time * (self.target - pressure);
+                (volume_scale.max(0.5).powf(1.0 / d), 1.0)
+            }
+            BarostatKind::MartynaTobiasKlein => {
+                let n = particles.len() as f64;
+                let volume = periodic_box.volume();
+                let kinetic: f64 = particles
+                    .iter()
+                    .map(|p| 0.5 * p.mass() * p.velocity().magnitude2())
+                    .sum();
+                // the box mass is chosen so it oscillates with a period of about the coupling
+                // time, whatever the temperature
+                let temperature = (2.0 * kinetic / (d * n)).max(1e-9);
+                let mass = (d * n + d) * temperature * self.coupling_time.powi(2);
+                let drive = d * volume * (pressure - self.target) + 2.0 * kinetic / n;
+                self.expansion_rate += drive / mass * dt;
+                (
+                    (self.expansion_rate * dt).exp(),
+                    (-(1.0 + 1.0 / n) * self.expansion_rate * dt).exp(),
+                )
+            }
+        };
+        let center = periodic_box.min + periodic_box.size / 2.0;
+        periodic_box.size *= scale;
+        periodic_box.min = center - periodic_box.size / 2.0;
+        particles.move_all(|_, position| center + (position - center) * scale);
+        for velocity in particles.velocities_mut() {
+            *velocity *= velocity_scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        integrator::{Integrator, Solvent},
+        particle::Particle,
+        simulation::Simulation,
+    };
+
+    /// An ideal gas with a kinetic energy of 100, at a pressure of 1 in its 10 by 10 box.
+    fn ideal_gas(kind: BarostatKind) -> Simulation {
+        let particles = (0..100)
+            .map(|i| {
+                let angle = i as f64 * 2.4;
+                Particle::new(
+                    cgmath::point2((i % 10) as f64 - 4.5, (i / 10) as f64 - 4.5),
+                    cgmath::vec2(angle.cos(), angle.sin()) * 2.0f64.sqrt(),
+                    1.0,
+                    0.0,
+                )
+            })
+            .collect();
+        let mut simulation = Simulation::new(particles, vec![]);
+        simulation.force_field.periodic_box = Some(PeriodicBox::centered(cgmath::vec2(10.0, 10.0)));
+        simulation.barostat = Some(Barostat::new(kind, 0.5, 0.5));
+        simulation
+    }
+
+    #[test]
+    fn ideal_gas_pressure_is_kinetic_energy_over_area() {
+        let simulation = ideal_gas(BarostatKind::Berendsen);
+        assert!((simulation.pressure().unwrap() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn berendsen_expands_to_the_target_pressure() {
+        let mut simulation = ideal_gas(BarostatKind::Berendsen);
+        for _ in 0..1000 {
+            simulation.step(0.01);
+        }
+        let periodic_box = simulation.force_field.periodic_box.unwrap();
+        assert!((periodic_box.size.x * periodic_box.size.y - 200.0).abs() < 1.0);
+        assert!((simulation.pressure().unwrap() - 0.5).abs() < 1e-2);
+        // the box is scaled about its centre, and the particles with it
+        assert_eq!(
+            periodic_box.min + periodic_box.size / 2.0,
+            cgmath::point2(0.0, 0.0)
+        );
+        assert!(simulation
+            .particles
+            .iter()
+            .all(|p| periodic_box.wrap(p.position()) == p.position()));
+    }
+
+    #[test]
+    fn mtk_averages_to_the_target_pressure() {
+        // left alone the box would oscillate forever, a heat bath lets it settle into the
+        // isothermal-isobaric ensemble, where an ideal gas averages to exactly the target
+        let mut simulation = ideal_gas(BarostatKind::MartynaTobiasKlein);
+        simulation.integrator = Integrator::Langevin;
+        simulation.solvent = Solvent {
+            friction: 1.0,
+            temperature: 1.0,
+        };
+        let mut sum = 0.0;
+        let steps = 8000;
+        for step in 0..steps {
+            simulation.step(0.01);
+            if step >= steps / 2 {
+                sum += simulation.pressure().unwrap();
+            }
+        }
+        let average = sum / (steps / 2) as f64;
+        assert!((average - 0.5).abs() < 0.05, "{}", average);
+    }
+}
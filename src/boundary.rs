@@ -0,0 +1,132 @@
+use cgmath::EuclideanSpace;
+
+use crate::dimension::Vector;
+
+/// Rectangular box that particles wrap around, with distances measured to the nearest
+/// periodic image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeriodicBox<V: Vector = cgmath::Vector2<f64>> {
+    pub min: V::Point,
+    pub size: V,
+}
+
+impl<V: Vector> PeriodicBox<V> {
+    pub fn centered(size: V) -> Self {
+        Self {
+            min: V::Point::from_vec(-size / 2.0),
+            size,
+        }
+    }
+
+    pub fn max(&self) -> V::Point {
+        self.min + self.size
+    }
+
+    /// Area in 2D, volume in 3D.
+    pub fn volume(&self) -> f64 {
+        self.size.product()
+    }
+
+    pub fn wrap(&self, p: V::Point) -> V::Point {
+        let offset = p - self.min;
+        self.min + V::from_fn(|i| offset[i].rem_euclid(self.size[i]))
+    }
+
+    pub fn minimum_image(&self, d: V) -> V {
+        V::from_fn(|i| d[i] - self.size[i] * (d[i] / self.size[i]).round())
+    }
+
+    /// Offsets of the periodic images of `p` that land within `margin` of the box, for drawing
+    /// things that stick out over an edge on the opposite side too.
+    pub fn ghost_offsets(&self, p: V::Point, margin: f64) -> Vec<V> {
+        let max = self.max();
+        let mut offsets = vec![V::zero()];
+        for i in 0..V::DIMENSION {
+            let mut shifts = vec![];
+            if p[i] - self.min[i] < margin {
+                shifts.push(self.size[i]);
+            }
+            if max[i] - p[i] < margin {
+                shifts.push(-self.size[i]);
+            }
+            let shifted: Vec<V> = offsets
+                .iter()
+                .flat_map(|&offset| {
+                    shifts.iter().map(move |&shift| {
+                        let mut offset = offset;
+                        offset[i] = shift;
+                        offset
+                    })
+                })
+                .collect();
+            offsets.extend(shifted);
+        }
+        offsets.remove(0);
+        offsets
+    }
+}
+
+/// Displacement from `b` to `a`, through the nearest periodic image if there is a box.
+pub fn displacement<V: Vector>(
+    periodic_box: Option<&PeriodicBox<V>>,
+    a: V::Point,
+    b: V::Point,
+) -> V {
+    match periodic_box {
+        Some(periodic_box) => periodic_box.minimum_image(a - b),
+        None => a - b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    #[test]
+    fn wraps_into_the_box() {
+        let periodic_box = PeriodicBox::centered(cgmath::vec2(10.0, 4.0));
+        let wrapped = periodic_box.wrap(cgmath::point2(6.0, -7.0));
+        assert!((wrapped.x - -4.0).abs() < 1e-12);
+        assert!((wrapped.y - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn minimum_image_picks_the_closest_copy() {
+        let periodic_box = PeriodicBox::centered(cgmath::vec2(10.0, 4.0));
+        let d = displacement(
+            Some(&periodic_box),
+            cgmath::point2(4.5, 1.5),
+            cgmath::point2(-4.5, -1.5),
+        );
+        assert!((d - cgmath::vec2(-1.0, -1.0)).x.abs() < 1e-12);
+        assert!((d - cgmath::vec2(-1.0, -1.0)).y.abs() < 1e-12);
+    }
+
+    #[test]
+    fn ghosts_in_the_corner() {
+        let periodic_box = PeriodicBox::centered(cgmath::vec2(10.0, 10.0));
+        assert_eq!(
+            periodic_box.ghost_offsets(cgmath::point2(0.0, 0.0), 0.5),
+            vec![]
+        );
+        assert_eq!(
+            periodic_box
+                .ghost_offsets(cgmath::point2(4.8, 4.9), 0.5)
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn wraps_and_ghosts_in_3d() {
+        let periodic_box = PeriodicBox::centered(cgmath::vec3(10.0, 4.0, 6.0));
+        assert_eq!(periodic_box.volume(), 240.0);
+        let wrapped = periodic_box.wrap(cgmath::point3(6.0, -7.0, 3.5));
+        assert!((wrapped - cgmath::point3(-4.0, 1.0, -2.5)).magnitude() < 1e-12);
+        // a corner of a cube has seven images nearby
+        let ghosts = periodic_box.ghost_offsets(cgmath::point3(4.8, 1.9, 2.9), 0.5);
+        assert_eq!(ghosts.len(), 7);
+        assert!(ghosts.contains(&cgmath::vec3(-10.0, -4.0, -6.0)));
+    }
+}
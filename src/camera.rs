@@ -20,6 +20,23 @@ impl Camera {
             * cgmath::Matrix4::from_translation(self.displacement.extend(0.0))
     }
 
+    /// How much of the world one pixel covers, in a window `width` pixels wide.
+    pub fn pixel_size(&self, width: u32) -> f32 {
+        2.0 / (self.scale * width as f32)
+    }
+
+    /// Opposite corners of the part of the world in view, in a window `width` by `height`
+    /// pixels.
+    pub fn visible_region(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> (cgmath::Point2<f32>, cgmath::Point2<f32>) {
+        let half_size = cgmath::vec2(1.0, height as f32 / width as f32) / self.scale;
+        let center = cgmath::Point2::from_vec(-self.displacement);
+        (center - half_size, center + half_size)
+    }
+
     pub fn new() -> Self {
         Self {
             displacement: Zero::zero(),
@@ -54,3 +71,105 @@ impl Camera {
         self.scale *= (-amount).exp();
     }
 }
+
+/// Vertical field of view of `OrbitCamera`, which also sets how much the orthographic view
+/// shows so switching between the two keeps the target the same size.
+const FIELD_OF_VIEW: cgmath::Deg<f32> = cgmath::Deg(45.0);
+
+/// Looks at a target from somewhere on a sphere around it, for 3D. Dragging swings it around
+/// the target and scrolling moves it closer or further away.
+#[derive(Debug)]
+pub struct OrbitCamera {
+    target: cgmath::Point3<f32>,
+    /// Around the y axis, in radians.
+    yaw: f32,
+    /// Above the xz plane, in radians.
+    pitch: f32,
+    distance: f32,
+    perspective: bool,
+    dragging: bool,
+}
+
+impl OrbitCamera {
+    pub fn new() -> Self {
+        Self {
+            target: cgmath::Point3::origin(),
+            yaw: 0.6,
+            pitch: 0.4,
+            distance: 30.0,
+            perspective: true,
+            dragging: false,
+        }
+    }
+
+    pub fn is_perspective(&self) -> bool {
+        self.perspective
+    }
+
+    /// Switches between perspective and orthographic projection.
+    pub fn toggle_projection(&mut self) {
+        self.perspective = !self.perspective;
+    }
+
+    fn eye(&self) -> cgmath::Point3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        self.target
+            + cgmath::vec3(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw) * self.distance
+    }
+
+    pub fn view_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::look_at_rh(self.eye(), self.target, cgmath::Vector3::unit_y())
+    }
+
+    /// OpenGL style, from -1 to 1 in z, in a window `width` by `height` pixels.
+    pub fn projection_matrix(&self, width: u32, height: u32) -> cgmath::Matrix4<f32> {
+        let aspect = width as f32 / height as f32;
+        let (near, far) = (0.1, 1000.0);
+        if self.perspective {
+            cgmath::perspective(FIELD_OF_VIEW, aspect, near, far)
+        } else {
+            let half_height = self.half_height();
+            let half_width = half_height * aspect;
+            cgmath::ortho(
+                -half_width,
+                half_width,
+                -half_height,
+                half_height,
+                near,
+                far,
+            )
+        }
+    }
+
+    /// Half of how tall the view is at the target.
+    fn half_height(&self) -> f32 {
+        self.distance * (FIELD_OF_VIEW / 2.0).tan()
+    }
+
+    /// How much of the world one pixel covers at the target, in a window `height` pixels
+    /// tall.
+    pub fn pixel_size(&self, height: u32) -> f32 {
+        2.0 * self.half_height() / height as f32
+    }
+
+    pub fn click_mouse(&mut self) {
+        self.dragging = true;
+    }
+
+    /// Swings around the target by however many pixels the mouse moved.
+    pub fn drag_mouse(&mut self, xrel: i32, yrel: i32) {
+        if self.dragging {
+            self.yaw -= xrel as f32 * 0.01;
+            self.pitch = (self.pitch + yrel as f32 * 0.01).clamp(-1.5, 1.5);
+        }
+    }
+
+    pub fn let_go_of_mouse(&mut self) {
+        self.dragging = false;
+    }
+
+    pub fn scroll(&mut self, amount: f32) {
+        self.distance *= amount.exp();
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{
+    boundary::{self, PeriodicBox},
+    dimension::Vector,
+    neighbor_list,
+    particle::ParticleStore,
+};
+
+/// How many times `Collisions::resolve` sweeps over the contacts, since pushing one pair apart
+/// can push another together in a dense cluster.
+const MAX_PASSES: usize = 4;
+
+/// Treats particles as hard spheres of their physical radius. After every step, overlapping
+/// pairs that are still approaching exchange an impulse along the line between their centres,
+/// and every overlapping pair is pushed apart without moving their centre of mass. Fast
+/// particles can pass right through each other in one step, so keep steps short enough that
+/// nothing moves more than about a radius.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collisions {
+    /// Ratio of the speeds the particles separate and approach with, 1 for perfectly elastic
+    /// billiard balls and less for granular matter that loses energy in every collision.
+    pub restitution: f64,
+}
+
+impl Collisions {
+    /// Returns how many impulses were exchanged.
+    pub fn resolve<V: Vector>(
+        &self,
+        particles: &mut ParticleStore<V>,
+        periodic_box: Option<&PeriodicBox<V>>,
+    ) -> usize {
+        let max_radius = particles.radii().iter().copied().fold(0.0, f64::max);
+        if max_radius <= 0.0 {
+            return 0;
+        }
+        let mut collisions = 0;
+        for _ in 0..MAX_PASSES {
+            let pairs = neighbor_list::cell_list_pairs(
+                particles.positions(),
+                2.0 * max_radius,
+                periodic_box,
+            );
+            let mut overlapping = false;
+            for (i, j) in pairs {
+                let (a, b) = (particles.get(i), particles.get(j));
+                let r = boundary::displacement(periodic_box, a.position(), b.position());
+                let distance = r.magnitude();
+                let overlap = a.radius() + b.radius() - distance;
+                if overlap <= 0.0 || distance == 0.0 {
+                    continue;
+                }
+                overlapping = true;
+                let normal = r / distance;
+                let (mass_i, mass_j) = (a.mass(), b.mass());
+                let total = mass_i + mass_j;
+
+                let approach = (a.velocity() - b.velocity()).dot(normal);
+                if approach < 0.0 {
+                    let impulse =
+                        normal * (-(1.0 + self.restitution) * approach * mass_i * mass_j / total);
+                    particles.get_mut(i).kick(impulse / mass_i);
+                    particles.get_mut(j).kick(-impulse / mass_j);
+                    collisions += 1;
+                }
+                particles
+                    .get_mut(i)
+                    .drift(normal * (overlap * mass_j / total));
+                particles
+                    .get_mut(j)
+                    .drift(-normal * (overlap * mass_i / total));
+            }
+            if !overlapping {
+                break;
+            }
+        }
+        collisions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        particle::Particle,
+        species::{Species, SpeciesId},
+    };
+    use cgmath::InnerSpace;
+
+    /// A hard ball with no Lennard-Jones attraction.
+    fn ball(x: f64, y: f64, vx: f64, vy: f64, mass: f64) -> Particle {
+        let species = Species::new("ball", mass, 0.0, [1.0, 1.0, 1.0]).with_radius(0.5);
+        Particle::of_species(
+            SpeciesId::NEUTRAL,
+            &species,
+            cgmath::point2(x, y),
+            cgmath::vec2(vx, vy),
+        )
+    }
+
+    fn momentum(particles: &ParticleStore) -> cgmath::Vector2<f64> {
+        particles.iter().map(|p| p.velocity() * p.mass()).sum()
+    }
+
+    fn kinetic_energy(particles: &ParticleStore) -> f64 {
+        particles
+            .iter()
+            .map(|p| 0.5 * p.mass() * p.velocity().magnitude2())
+            .sum()
+    }
+
+    fn center_of_mass(particles: &ParticleStore) -> cgmath::Vector2<f64> {
+        let mass: f64 = particles.iter().map(|p| p.mass()).sum();
+        particles
+            .iter()
+            .map(|p| (p.position() - cgmath::point2(0.0, 0.0)) * p.mass())
+            .sum::<cgmath::Vector2<f64>>()
+            / mass
+    }
+
+    #[test]
+    fn elastic_head_on_collision_swaps_equal_velocities() {
+        let mut particles = vec![
+            ball(-0.45, 0.0, 1.0, 0.0, 1.0),
+            ball(0.45, 0.0, -0.5, 0.0, 1.0),
+        ]
+        .into_iter()
+        .collect::<ParticleStore>();
+        let elastic = Collisions { restitution: 1.0 };
+        assert_eq!(elastic.resolve(&mut particles, None), 1);
+        assert!((particles.get(0).velocity() - cgmath::vec2(-0.5, 0.0)).magnitude() < 1e-12);
+        assert!((particles.get(1).velocity() - cgmath::vec2(1.0, 0.0)).magnitude() < 1e-12);
+        // and they are pushed apart until they just touch
+        let gap = particles.get(1).position() - particles.get(0).position();
+        assert!((gap.magnitude() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn inelastic_head_on_collision_conserves_momentum() {
+        let mut particles = vec![
+            ball(-0.45, 0.0, 2.0, 0.0, 3.0),
+            ball(0.45, 0.0, -1.0, 0.0, 1.0),
+        ]
+        .into_iter()
+        .collect::<ParticleStore>();
+        let (momentum_before, center_before) = (momentum(&particles), center_of_mass(&particles));
+        let approach = particles.get(0).velocity().x - particles.get(1).velocity().x;
+        Collisions { restitution: 0.5 }.resolve(&mut particles, None);
+        assert!((momentum(&particles) - momentum_before).magnitude() < 1e-12);
+        assert!((center_of_mass(&particles) - center_before).magnitude() < 1e-12);
+        let separation = particles.get(1).velocity().x - particles.get(0).velocity().x;
+        assert!((separation - 0.5 * approach).abs() < 1e-12);
+    }
+
+    #[test]
+    fn glancing_collision_conserves_momentum_and_energy() {
+        // equal masses hitting off-centre leave at right angles to each other
+        let mut particles = vec![
+            ball(-0.5, -0.3, 1.5, 0.0, 1.0),
+            ball(0.3, 0.2, 0.0, 0.0, 1.0),
+        ]
+        .into_iter()
+        .collect::<ParticleStore>();
+        let (momentum_before, energy_before) = (momentum(&particles), kinetic_energy(&particles));
+        assert_eq!(
+            Collisions { restitution: 1.0 }.resolve(&mut particles, None),
+            1
+        );
+        assert!((momentum(&particles) - momentum_before).magnitude() < 1e-12);
+        assert!((kinetic_energy(&particles) - energy_before).abs() < 1e-12);
+        assert!(
+            particles
+                .get(0)
+                .velocity()
+                .dot(particles.get(1).velocity())
+                .abs()
+                < 1e-12
+        );
+        assert!(particles.get(1).velocity().magnitude() > 0.0);
+
+        // unequal masses still conserve momentum exactly
+        let mut particles = vec![
+            ball(-0.5, -0.3, 1.5, 0.2, 2.5),
+            ball(0.3, 0.2, -0.4, 0.0, 0.7),
+        ]
+        .into_iter()
+        .collect::<ParticleStore>();
+        let momentum_before = momentum(&particles);
+        Collisions { restitution: 0.7 }.resolve(&mut particles, None);
+        assert!((momentum(&particles) - momentum_before).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn separating_and_distant_pairs_are_left_moving() {
+        let mut particles = vec![
+            ball(-0.45, 0.0, -1.0, 0.0, 1.0),
+            ball(0.45, 0.0, 1.0, 0.0, 1.0),
+        ]
+        .into_iter()
+        .collect::<ParticleStore>();
+        assert_eq!(
+            Collisions { restitution: 1.0 }.resolve(&mut particles, None),
+            0
+        );
+        assert_eq!(particles.get(0).velocity(), cgmath::vec2(-1.0, 0.0));
+
+        let mut particles = vec![
+            ball(-1.0, 0.0, 1.0, 0.0, 1.0),
+            ball(1.0, 0.0, -1.0, 0.0, 1.0),
+        ]
+        .into_iter()
+        .collect::<ParticleStore>();
+        assert_eq!(
+            Collisions { restitution: 1.0 }.resolve(&mut particles, None),
+            0
+        );
+        assert_eq!(particles.get(0).position(), cgmath::point2(-1.0, 0.0));
+    }
+
+    #[test]
+    fn collides_across_a_periodic_edge() {
+        let periodic_box = PeriodicBox::centered(cgmath::vec2(10.0, 10.0));
+        let mut particles = vec![
+            ball(4.6, 0.0, 1.0, 0.0, 1.0),
+            ball(-4.6, 0.0, -1.0, 0.0, 1.0),
+        ]
+        .into_iter()
+        .collect::<ParticleStore>();
+        assert_eq!(
+            Collisions { restitution: 1.0 }.resolve(&mut particles, Some(&periodic_box)),
+            1
+        );
+        assert_eq!(particles.get(0).velocity(), cgmath::vec2(-1.0, 0.0));
+    }
+
+    #[test]
+    fn granular_gas_cools_without_gaining_momentum() {
+        let scene = crate::scene::granular_gas();
+        let mut simulation = crate::simulation::Simulation::new(scene.particles, vec![]);
+        simulation.collisions = scene.collisions;
+        simulation.force_field.periodic_box = scene.periodic_box;
+        let (momentum_before, energy_before) =
+            (momentum(&simulation.particles), simulation.kinetic_energy());
+        for _ in 0..600 {
+            simulation.step(1.0 / 60.0);
+        }
+        assert!((momentum(&simulation.particles) - momentum_before).magnitude() < 1e-9);
+        assert!(simulation.kinetic_energy() < 0.9 * energy_before);
+    }
+}
@@ -0,0 +1,356 @@
+use cgmath::EuclideanSpace;
+use futures::executor::block_on;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    boundary::PeriodicBox,
+    dimension::Vector,
+    particle::ParticleStore,
+    potential::{Coulomb, LennardJones},
+};
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// A particle as the compute shaders keep it, in single precision. It starts with the same
+/// position, radius and species the draw needs, so the storage buffer doubles as the instance
+/// buffer; `INSTANCE_ATTRIBUTES` says where to find them.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct GpuParticle {
+    /// With z left at 0 in 2D.
+    position: [f32; 3],
+    radius: f32,
+    velocity: [f32; 3],
+    mass: f32,
+    acceleration: [f32; 3],
+    charge: f32,
+    sigma: f32,
+    epsilon: f32,
+    species: u32,
+    padding: u32,
+}
+
+unsafe impl bytemuck::Zeroable for GpuParticle {}
+unsafe impl bytemuck::Pod for GpuParticle {}
+
+/// The particle shaders' position, radius and species inputs, at locations 1 to 3, read
+/// straight out of `GpuSimulation::particle_buffer`.
+pub const INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 3] = [
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float32x3,
+        offset: 0,
+        shader_location: 1,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float32,
+        offset: 12,
+        shader_location: 2,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Uint32,
+        offset: 56,
+        shader_location: 3,
+    },
+];
+
+pub const INSTANCE_STRIDE: u64 = std::mem::size_of::<GpuParticle>() as u64;
+
+/// The shaders' uniform block, laid out by std140 rules.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Parameters {
+    box_min: [f32; 3],
+    count: u32,
+    /// Zero along the axes that aren't periodic, which is all of them in open space.
+    box_size: [f32; 3],
+    dt: f32,
+    coulomb_k: f32,
+    softening: f32,
+    cutoff: f32,
+    padding: f32,
+}
+
+unsafe impl bytemuck::Zeroable for Parameters {}
+unsafe impl bytemuck::Pod for Parameters {}
+
+/// Velocity Verlet with direct Coulomb and Lennard-Jones forces between every pair, run by
+/// compute shaders on particles that never leave the GPU. Bonds, walls, fields, gravity,
+/// collisions and thermostats are left out, as is the rest of the pair potentials; the forces
+/// are summed in single precision, so trajectories drift apart from the CPU's over time.
+pub struct GpuSimulation {
+    particles: wgpu::Buffer,
+    parameters: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    drift_pipeline: wgpu::ComputePipeline,
+    kick_pipeline: wgpu::ComputePipeline,
+    base: Parameters,
+}
+
+impl GpuSimulation {
+    /// Uploads `particles`, drawn `radii` across, and works out their first accelerations.
+    pub fn new<V: Vector>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particles: &ParticleStore<V>,
+        radii: &[f64],
+        periodic_box: Option<&PeriodicBox<V>>,
+        coulomb: &Coulomb,
+        lennard_jones: &LennardJones,
+    ) -> Self {
+        let uploaded: Vec<_> = particles
+            .iter()
+            .zip(particles.render_positions())
+            .zip(radii)
+            .map(|((p, &position), &radius)| GpuParticle {
+                position,
+                radius: radius as f32,
+                velocity: to_f32(p.velocity()),
+                mass: p.mass() as f32,
+                acceleration: [0.0; 3],
+                charge: p.charge() as f32,
+                sigma: p.lennard_jones().sigma as f32,
+                epsilon: p.lennard_jones().epsilon as f32,
+                species: p.species().index(),
+                padding: 0,
+            })
+            .collect();
+        let particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("gpu particle buffer"),
+            contents: bytemuck::cast_slice(&uploaded[..]),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_SRC,
+        });
+        let base = Parameters {
+            box_min: periodic_box.map_or([0.0; 3], |b| V::render_position(b.min)),
+            count: particles.len() as u32,
+            box_size: periodic_box.map_or([0.0; 3], |b| to_f32(b.size)),
+            dt: 0.0,
+            coulomb_k: coulomb.k as f32,
+            softening: coulomb.softening as f32,
+            cutoff: lennard_jones.cutoff as f32,
+            padding: 0.0,
+        };
+        let parameters = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("gpu parameter buffer"),
+            contents: bytemuck::bytes_of(&base),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gpu physics layout"),
+            entries: &[
+                buffer_entry(0, wgpu::BufferBindingType::Storage { read_only: false }),
+                buffer_entry(1, wgpu::BufferBindingType::Uniform),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gpu physics bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: particle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: parameters.as_entire_binding(),
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("gpu physics pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let drift = device.create_shader_module(&wgpu::include_spirv!("drift.comp.spirv"));
+        let kick = device.create_shader_module(&wgpu::include_spirv!("kick.comp.spirv"));
+        let pipeline = |label, module| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                module,
+                entry_point: "main",
+            })
+        };
+
+        let simulation = Self {
+            particles: particle_buffer,
+            parameters,
+            bind_group,
+            drift_pipeline: pipeline("drift pipeline", &drift),
+            kick_pipeline: pipeline("kick pipeline", &kick),
+            base,
+        };
+        // a kick of no time at all, just to fill in the accelerations
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("gpu physics encoder"),
+        });
+        simulation.dispatch(&mut encoder, &simulation.kick_pipeline);
+        queue.submit([encoder.finish()]);
+        simulation
+    }
+
+    fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("gpu physics pass"),
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch(self.base.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Submits one velocity Verlet step of `dt`.
+    pub fn step(&self, device: &wgpu::Device, queue: &wgpu::Queue, dt: f64) {
+        let parameters = Parameters {
+            dt: dt as f32,
+            ..self.base
+        };
+        queue.write_buffer(&self.parameters, 0, bytemuck::bytes_of(&parameters));
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("gpu physics encoder"),
+        });
+        // separate passes, so every drift finishes before any force is worked out
+        self.dispatch(&mut encoder, &self.drift_pipeline);
+        self.dispatch(&mut encoder, &self.kick_pipeline);
+        queue.submit([encoder.finish()]);
+    }
+
+    /// The particles, laid out for `INSTANCE_ATTRIBUTES`.
+    pub fn particle_buffer(&self) -> &wgpu::Buffer {
+        &self.particles
+    }
+
+    pub fn len(&self) -> u32 {
+        self.base.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.base.count == 0
+    }
+
+    /// Copies positions and velocities back into `particles`, waiting for the GPU to finish.
+    pub fn download<V: Vector>(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particles: &mut ParticleStore<V>,
+    ) {
+        let size = self.base.count as u64 * INSTANCE_STRIDE;
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu particle readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("gpu readback encoder"),
+        });
+        encoder.copy_buffer_to_buffer(&self.particles, 0, &staging, 0, size);
+        queue.submit([encoder.finish()]);
+
+        let slice = staging.slice(..);
+        let mapped = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        block_on(mapped).expect("failed to read particles back from the gpu");
+        {
+            let data = slice.get_mapped_range();
+            let downloaded: &[GpuParticle] = bytemuck::cast_slice(&data);
+            particles.move_all(|i, _| {
+                V::Point::from_vec(V::from_fn(|axis| downloaded[i].position[axis] as f64))
+            });
+            for (velocity, gpu) in particles.velocities_mut().iter_mut().zip(downloaded) {
+                *velocity = V::from_fn(|axis| gpu.velocity[axis] as f64);
+            }
+        }
+        staging.unmap();
+    }
+}
+
+fn to_f32<V: Vector>(v: V) -> [f32; 3] {
+    V::render_position(V::Point::from_vec(v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        integrator::Integrator,
+        scene,
+        simulation::{Simulation, StepControl},
+    };
+    use cgmath::InnerSpace;
+
+    /// A software adapter, so the comparison runs the same on any machine with a driver.
+    /// `None` where there is no such thing, like a machine without a Vulkan driver at all.
+    fn fallback_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: true,
+        }))?;
+        block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()
+    }
+
+    #[test]
+    fn matches_the_cpu_backend() {
+        let (device, queue) = match fallback_device() {
+            Some(device) => device,
+            None => {
+                eprintln!("skipped: no fallback adapter, so there's no GPU to compare against");
+                return;
+            }
+        };
+        // the ionic crystal, melting with the periodic gas's velocities
+        let mut scene = scene::ionic_crystal();
+        for (particle, gas) in scene
+            .particles
+            .iter_mut()
+            .zip(scene::periodic_gas().particles)
+        {
+            particle.set_velocity(gas.velocity());
+        }
+        let (coulomb, lennard_jones) = (Coulomb::default(), LennardJones::default());
+        let mut cpu = Simulation::new(
+            scene.particles,
+            vec![Box::new(coulomb), Box::new(lennard_jones)],
+        );
+        let gpu = GpuSimulation::new(
+            &device,
+            &queue,
+            &cpu.particles,
+            cpu.particles.radii(),
+            scene.periodic_box.as_ref(),
+            &coulomb,
+            &lennard_jones,
+        );
+        cpu.force_field.periodic_box = scene.periodic_box;
+        cpu.integrator = Integrator::VelocityVerlet;
+        cpu.step_control = StepControl::Fixed;
+
+        let dt = 1.0 / 600.0;
+        for _ in 0..60 {
+            cpu.step(dt);
+            gpu.step(&device, &queue, dt);
+        }
+        let mut downloaded = cpu.particles.clone();
+        gpu.download(&device, &queue, &mut downloaded);
+        let periodic_box = cpu.force_field.periodic_box.unwrap();
+        for (cpu, gpu) in cpu.particles.iter().zip(&downloaded) {
+            let apart = periodic_box.minimum_image(gpu.position() - cpu.position());
+            assert!(apart.magnitude() < 1e-3, "{:?}", apart);
+            assert!((gpu.velocity() - cpu.velocity()).magnitude() < 1e-2);
+        }
+    }
+}
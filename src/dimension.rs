@@ -0,0 +1,139 @@
+use std::{
+    fmt::Debug,
+    iter::Sum,
+    ops::{AddAssign, DivAssign, Mul, MulAssign, Neg, SubAssign},
+};
+
+use cgmath::{Array, EuclideanSpace, InnerSpace, Zero};
+
+use crate::{boundary::PeriodicBox, particle::ParticleStore};
+
+/// The vectors particles move with, which decide how many dimensions a simulation runs in.
+/// Everything generic over it defaults to `Vector2<f64>`; `Vector3<f64>` runs the same physics
+/// in 3D.
+pub trait Vector:
+    InnerSpace<Scalar = f64>
+    + Array<Element = f64>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign<f64>
+    + DivAssign<f64>
+    + Copy
+    + Debug
+    + PartialEq
+    + Sum
+    + Send
+    + Sync
+    + 'static
+{
+    type Point: EuclideanSpace<Scalar = f64, Diff = Self>
+        + Array<Element = f64>
+        + AddAssign<Self>
+        + SubAssign<Self>
+        + Copy
+        + Debug
+        + PartialEq
+        + Send
+        + Sync;
+    /// Pseudovectors like the magnetic field, which in 2D can only point out of the plane and
+    /// are just a number along z.
+    type Axial: Copy + Debug + PartialEq + Zero + Mul<f64, Output = Self::Axial>;
+
+    const DIMENSION: usize;
+
+    /// `self x axial`.
+    fn cross(self, axial: Self::Axial) -> Self;
+
+    fn axial_magnitude2(axial: Self::Axial) -> f64;
+
+    /// The part of the vector in the xy plane.
+    fn xy(self) -> cgmath::Vector2<f64>;
+
+    /// A vector in the xy plane.
+    fn from_xy(xy: cgmath::Vector2<f64>) -> Self;
+
+    /// The same particles and box as 2D ones, for the summations that only exist in 2D.
+    fn planar<'a>(
+        particles: &'a ParticleStore<Self>,
+        periodic_box: &'a PeriodicBox<Self>,
+    ) -> Option<(&'a ParticleStore, &'a PeriodicBox)>;
+
+    /// `point` in single precision, for drawing. 2D points sit at z = 0.
+    fn render_position(point: Self::Point) -> [f32; 3] {
+        let mut position = [0.0; 3];
+        for (i, p) in position.iter_mut().enumerate().take(Self::DIMENSION) {
+            *p = point[i] as f32;
+        }
+        position
+    }
+
+    /// A vector with every component from `f`, in order.
+    fn from_fn(mut f: impl FnMut(usize) -> f64) -> Self {
+        let mut v = Self::zero();
+        for i in 0..Self::DIMENSION {
+            v[i] = f(i);
+        }
+        v
+    }
+}
+
+impl Vector for cgmath::Vector2<f64> {
+    type Point = cgmath::Point2<f64>;
+    type Axial = f64;
+
+    const DIMENSION: usize = 2;
+
+    fn cross(self, axial: f64) -> Self {
+        cgmath::vec2(self.y * axial, -self.x * axial)
+    }
+
+    fn axial_magnitude2(axial: f64) -> f64 {
+        axial * axial
+    }
+
+    fn xy(self) -> cgmath::Vector2<f64> {
+        self
+    }
+
+    fn from_xy(xy: cgmath::Vector2<f64>) -> Self {
+        xy
+    }
+
+    fn planar<'a>(
+        particles: &'a ParticleStore,
+        periodic_box: &'a PeriodicBox,
+    ) -> Option<(&'a ParticleStore, &'a PeriodicBox)> {
+        Some((particles, periodic_box))
+    }
+}
+
+impl Vector for cgmath::Vector3<f64> {
+    type Point = cgmath::Point3<f64>;
+    type Axial = cgmath::Vector3<f64>;
+
+    const DIMENSION: usize = 3;
+
+    fn cross(self, axial: Self) -> Self {
+        cgmath::Vector3::cross(self, axial)
+    }
+
+    fn axial_magnitude2(axial: Self) -> f64 {
+        axial.magnitude2()
+    }
+
+    fn xy(self) -> cgmath::Vector2<f64> {
+        self.truncate()
+    }
+
+    fn from_xy(xy: cgmath::Vector2<f64>) -> Self {
+        xy.extend(0.0)
+    }
+
+    fn planar<'a>(
+        _: &'a ParticleStore<Self>,
+        _: &'a PeriodicBox<Self>,
+    ) -> Option<(&'a ParticleStore, &'a PeriodicBox)> {
+        None
+    }
+}
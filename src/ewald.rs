@@ -0,0 +1,392 @@
+use cgmath::{EuclideanSpace, InnerSpace};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::{boundary::PeriodicBox, neighbor_list::cell_list_pairs, particle::ParticleStore};
+
+/// How a periodic Coulomb sum is split between a short range part summed pair by pair and a
+/// smooth long range part summed in reciprocal space. A larger `alpha` moves more of the work
+/// into reciprocal space, the real space part has decayed by about `erfc(alpha * real_cutoff)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EwaldSplit {
+    pub alpha: f64,
+    /// Clamped to half the box so only the nearest image of each pair is needed.
+    pub real_cutoff: f64,
+}
+
+/// Fourier transform of the long range part `erf(alpha r) / r` of a 1/r interaction confined
+/// to a plane.
+fn influence(k: f64, alpha: f64) -> f64 {
+    2.0 * std::f64::consts::PI * libm::erfc(k / (2.0 * alpha)) / k
+}
+
+/// Pair sum of `1 / sqrt(r^2 + softening^2) - erf(alpha r) / r` over the nearest images,
+/// which is what is left of a softened Coulomb interaction once the reciprocal part is taken
+/// out. Adds the forces for unit coupling to `forces` and returns the energy.
+pub fn real_space(
+    particles: &ParticleStore,
+    periodic_box: &PeriodicBox,
+    split: EwaldSplit,
+    softening: f64,
+    forces: &mut [cgmath::Vector2<f64>],
+) -> f64 {
+    let alpha = split.alpha;
+    let cutoff = split
+        .real_cutoff
+        .min(periodic_box.size.x.min(periodic_box.size.y) / 2.0);
+    let (positions, charges) = (particles.positions(), particles.charges());
+    let eps2 = softening * softening;
+    let gaussian = 2.0 * alpha / std::f64::consts::PI.sqrt();
+    let mut energy = 0.0;
+    for (i, j) in cell_list_pairs(positions, cutoff, Some(periodic_box)) {
+        let qq = charges[i] * charges[j];
+        let r = periodic_box.minimum_image(positions[i] - positions[j]);
+        let d2 = r.magnitude2();
+        if d2 == 0.0 {
+            if softening > 0.0 {
+                energy += qq * (1.0 / softening - gaussian);
+            }
+            continue;
+        }
+        let d = d2.sqrt();
+        let soft = 1.0 / (d2 + eps2).sqrt();
+        let erfc = libm::erfc(alpha * d);
+        // written as the softening correction plus the erfc term so that nothing cancels
+        // when there is no softening
+        energy += qq * (soft - 1.0 / d + erfc / d);
+        let magnitude = soft * soft * soft * d - 1.0 / d2
+            + erfc / d2
+            + gaussian * (-alpha * alpha * d2).exp() / d;
+        let force = r * (qq * magnitude / d);
+        forces[i] += force;
+        forces[j] -= force;
+    }
+    energy
+}
+
+/// Removes each charge's interaction with its own share of the reciprocal sum, and adds the
+/// energy of the uniform background that neutralises a system with a net charge.
+pub fn self_energy(particles: &ParticleStore, periodic_box: &PeriodicBox, alpha: f64) -> f64 {
+    let pi = std::f64::consts::PI;
+    let total: f64 = particles.charges().iter().sum();
+    let squares: f64 = particles.charges().iter().map(|q| q * q).sum();
+    let area = periodic_box.size.x * periodic_box.size.y;
+    -alpha / pi.sqrt() * squares - pi.sqrt() * total * total / (alpha * area)
+}
+
+/// Ewald's reciprocal space sum over every wave vector with at most `k_max` periods across the
+/// box in each direction. Adds the forces for unit coupling and returns the energy.
+pub fn reciprocal_sum(
+    particles: &ParticleStore,
+    periodic_box: &PeriodicBox,
+    alpha: f64,
+    k_max: u32,
+    forces: &mut [cgmath::Vector2<f64>],
+) -> f64 {
+    let area = periodic_box.size.x * periodic_box.size.y;
+    let k_max = k_max as i64;
+    let mut phases = vec![(0.0, 0.0); particles.len()];
+    let mut energy = 0.0;
+    for nx in 0..=k_max {
+        for ny in -k_max..=k_max {
+            // k and -k contribute the same, so only half of them are summed and counted twice
+            if nx == 0 && ny <= 0 {
+                continue;
+            }
+            let k = cgmath::vec2(
+                2.0 * std::f64::consts::PI * nx as f64 / periodic_box.size.x,
+                2.0 * std::f64::consts::PI * ny as f64 / periodic_box.size.y,
+            );
+            let g = influence(k.magnitude(), alpha);
+            let (mut re, mut im) = (0.0, 0.0);
+            for (phase, particle) in phases.iter_mut().zip(particles) {
+                *phase = k.dot(particle.position().to_vec()).sin_cos();
+                re += particle.charge() * phase.1;
+                im += particle.charge() * phase.0;
+            }
+            energy += g * (re * re + im * im) / area;
+            for ((sin, cos), (particle, force)) in
+                phases.iter().zip(particles.iter().zip(&mut *forces))
+            {
+                *force += k * (2.0 * g / area * particle.charge() * (re * sin - im * cos));
+            }
+        }
+    }
+    energy
+}
+
+/// Mesh points and weights of the triangular-shaped cloud assignment along one axis.
+fn stencil(x: f64, min: f64, spacing: f64, mesh: usize) -> [(usize, f64); 3] {
+    let u = (x - min) / spacing;
+    let nearest = u.round();
+    let dx = u - nearest;
+    let weights = [
+        0.5 * (0.5 - dx) * (0.5 - dx),
+        0.75 - dx * dx,
+        0.5 * (0.5 + dx) * (0.5 + dx),
+    ];
+    let mut points = [(0, 0.0); 3];
+    for (offset, (point, weight)) in points.iter_mut().zip(weights.iter()).enumerate() {
+        let index = (nearest as i64 + offset as i64 - 1).rem_euclid(mesh as i64);
+        *point = (index as usize, *weight);
+    }
+    points
+}
+
+/// Fourier transform of the assignment along one axis, for the wave number `n` out of `mesh`.
+fn assignment_transform(n: i64, mesh: usize) -> f64 {
+    if n == 0 {
+        return 1.0;
+    }
+    let x = std::f64::consts::PI * n as f64 / mesh as f64;
+    (x.sin() / x).powi(3)
+}
+
+fn transpose(data: &[Complex<f64>], mesh: usize) -> Vec<Complex<f64>> {
+    (0..mesh * mesh)
+        .map(|i| data[(i % mesh) * mesh + i / mesh])
+        .collect()
+}
+
+/// 2D transform of a row-major `mesh` x `mesh` grid.
+fn fft2(data: &mut Vec<Complex<f64>>, mesh: usize, fft: &dyn Fft<f64>) {
+    fft.process(data);
+    let mut columns = transpose(data, mesh);
+    fft.process(&mut columns);
+    *data = transpose(&columns, mesh);
+}
+
+/// Particle-particle particle-mesh version of the reciprocal sum: charges are spread onto a
+/// `mesh` x `mesh` grid, the field is solved with FFTs and interpolated back. Adds the forces
+/// for unit coupling and returns the energy.
+pub fn reciprocal_mesh(
+    particles: &ParticleStore,
+    periodic_box: &PeriodicBox,
+    alpha: f64,
+    mesh: usize,
+    forces: &mut [cgmath::Vector2<f64>],
+) -> f64 {
+    let area = periodic_box.size.x * periodic_box.size.y;
+    let spacing = periodic_box.size / mesh as f64;
+    let stencils: Vec<_> = particles
+        .iter()
+        .map(|p| {
+            let p = periodic_box.wrap(p.position());
+            (
+                stencil(p.x, periodic_box.min.x, spacing.x, mesh),
+                stencil(p.y, periodic_box.min.y, spacing.y, mesh),
+            )
+        })
+        .collect();
+
+    let mut density = vec![Complex::new(0.0, 0.0); mesh * mesh];
+    for (particle, (xs, ys)) in particles.iter().zip(&stencils) {
+        for &(y, wy) in ys {
+            for &(x, wx) in xs {
+                density[y * mesh + x].re += particle.charge() * wx * wy;
+            }
+        }
+    }
+    let mut planner = FftPlanner::new();
+    fft2(&mut density, mesh, &*planner.plan_fft_forward(mesh));
+
+    let wave_number = |index: usize| {
+        if index <= mesh / 2 {
+            index as i64
+        } else {
+            index as i64 - mesh as i64
+        }
+    };
+    let mut energy = 0.0;
+    let mut field_x = vec![Complex::new(0.0, 0.0); mesh * mesh];
+    let mut field_y = vec![Complex::new(0.0, 0.0); mesh * mesh];
+    for b in 0..mesh {
+        for a in 0..mesh {
+            let (nx, ny) = (wave_number(a), wave_number(b));
+            if nx == 0 && ny == 0 {
+                continue;
+            }
+            let k = cgmath::vec2(
+                2.0 * std::f64::consts::PI * nx as f64 / periodic_box.size.x,
+                2.0 * std::f64::consts::PI * ny as f64 / periodic_box.size.y,
+            );
+            // divide out the smoothing from assigning the charges and interpolating the field
+            let w = assignment_transform(nx, mesh) * assignment_transform(ny, mesh);
+            let g = influence(k.magnitude(), alpha) / (area * w * w);
+            let rho = density[b * mesh + a];
+            energy += 0.5 * g * rho.norm_sqr();
+            // E = -grad phi, the Nyquist terms have no matching negative frequency so they
+            // can't contribute to a real gradient
+            let potential = rho * g;
+            if 2 * a != mesh {
+                field_x[b * mesh + a] = potential * Complex::new(0.0, -k.x);
+            }
+            if 2 * b != mesh {
+                field_y[b * mesh + a] = potential * Complex::new(0.0, -k.y);
+            }
+        }
+    }
+    let inverse = planner.plan_fft_inverse(mesh);
+    fft2(&mut field_x, mesh, &*inverse);
+    fft2(&mut field_y, mesh, &*inverse);
+
+    for ((particle, (xs, ys)), force) in particles.iter().zip(&stencils).zip(forces) {
+        for &(y, wy) in ys {
+            for &(x, wx) in xs {
+                let i = y * mesh + x;
+                *force +=
+                    cgmath::vec2(field_x[i].re, field_y[i].re) * (particle.charge() * wx * wy);
+            }
+        }
+    }
+    energy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    /// Madelung constant of a square lattice of alternating unit charges.
+    const SQUARE_MADELUNG: f64 = 1.615_542_626_712_824_7;
+
+    fn rock_salt_layer(side: usize) -> (ParticleStore, PeriodicBox) {
+        let particles = (0..side * side)
+            .map(|i| {
+                let (x, y) = (i % side, i / side);
+                let charge = if (x + y) % 2 == 0 { 1.0 } else { -1.0 };
+                Particle::new(
+                    cgmath::point2(x as f64, y as f64),
+                    cgmath::vec2(0.0, 0.0),
+                    1.0,
+                    charge,
+                )
+            })
+            .collect();
+        let periodic_box = PeriodicBox {
+            min: cgmath::point2(-0.5, -0.5),
+            size: cgmath::vec2(side as f64, side as f64),
+        };
+        (particles, periodic_box)
+    }
+
+    fn random_ions(count: usize) -> (ParticleStore, PeriodicBox) {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let periodic_box = PeriodicBox::centered(cgmath::vec2(10.0, 8.0));
+        let particles = (0..count)
+            .map(|i| {
+                let p = cgmath::point2(next() * 10.0 - 5.0, next() * 8.0 - 4.0);
+                let charge = if i % 2 == 0 { 1.0 } else { -1.0 };
+                Particle::new(p, cgmath::vec2(0.0, 0.0), 1.0, charge)
+            })
+            .collect();
+        (particles, periodic_box)
+    }
+
+    fn ewald(
+        particles: &ParticleStore,
+        periodic_box: &PeriodicBox,
+        alpha: f64,
+    ) -> (f64, Vec<cgmath::Vector2<f64>>) {
+        let split = EwaldSplit {
+            alpha,
+            real_cutoff: 4.0,
+        };
+        let mut forces = vec![cgmath::vec2(0.0, 0.0); particles.len()];
+        let energy = real_space(particles, periodic_box, split, 0.0, &mut forces)
+            + reciprocal_sum(particles, periodic_box, alpha, 20, &mut forces)
+            + self_energy(particles, periodic_box, alpha);
+        (energy, forces)
+    }
+
+    fn particle_mesh(
+        particles: &ParticleStore,
+        periodic_box: &PeriodicBox,
+        mesh: usize,
+    ) -> (f64, Vec<cgmath::Vector2<f64>>) {
+        let split = EwaldSplit {
+            alpha: 1.2,
+            real_cutoff: 4.0,
+        };
+        let mut forces = vec![cgmath::vec2(0.0, 0.0); particles.len()];
+        let energy = real_space(particles, periodic_box, split, 0.0, &mut forces)
+            + reciprocal_mesh(particles, periodic_box, split.alpha, mesh, &mut forces)
+            + self_energy(particles, periodic_box, split.alpha);
+        (energy, forces)
+    }
+
+    #[test]
+    fn ewald_reproduces_the_square_lattice_madelung_constant() {
+        let (particles, periodic_box) = rock_salt_layer(8);
+        let (energy, forces) = ewald(&particles, &periodic_box, 1.2);
+        let madelung = -2.0 * energy / particles.len() as f64;
+        assert!((madelung - SQUARE_MADELUNG).abs() < 1e-8, "{}", madelung);
+        // every ion sits at a centre of symmetry
+        assert!(forces.iter().all(|f| f.magnitude() < 1e-8));
+    }
+
+    #[test]
+    fn particle_mesh_reproduces_the_square_lattice_madelung_constant() {
+        let (particles, periodic_box) = rock_salt_layer(8);
+        let (energy, _) = particle_mesh(&particles, &periodic_box, 32);
+        let madelung = -2.0 * energy / particles.len() as f64;
+        assert!((madelung - SQUARE_MADELUNG).abs() < 1e-3, "{}", madelung);
+    }
+
+    #[test]
+    fn ewald_does_not_depend_on_the_split() {
+        let (particles, periodic_box) = random_ions(40);
+        let (coarse, coarse_forces) = ewald(&particles, &periodic_box, 0.9);
+        let (fine, fine_forces) = ewald(&particles, &periodic_box, 1.3);
+        assert!(
+            (coarse - fine).abs() < 1e-6 * fine.abs(),
+            "{} {}",
+            coarse,
+            fine
+        );
+        for (a, b) in coarse_forces.iter().zip(&fine_forces) {
+            assert!((a - b).magnitude() < 1e-5 * (1.0 + b.magnitude()));
+        }
+    }
+
+    #[test]
+    fn ewald_forces_are_the_energy_gradient() {
+        let (mut particles, periodic_box) = random_ions(20);
+        let (_, forces) = ewald(&particles, &periodic_box, 1.1);
+        let h = 1e-5;
+        for i in [0, 7, 13].iter().copied() {
+            particles.get_mut(i).drift(cgmath::vec2(h, 0.0));
+            let plus = ewald(&particles, &periodic_box, 1.1).0;
+            particles.get_mut(i).drift(cgmath::vec2(-2.0 * h, 0.0));
+            let minus = ewald(&particles, &periodic_box, 1.1).0;
+            particles.get_mut(i).drift(cgmath::vec2(h, 0.0));
+            let numeric = -(plus - minus) / (2.0 * h);
+            assert!((numeric - forces[i].x).abs() < 1e-5 * (1.0 + numeric.abs()));
+        }
+    }
+
+    #[test]
+    fn particle_mesh_matches_ewald() {
+        let (particles, periodic_box) = random_ions(60);
+        let (exact, exact_forces) = ewald(&particles, &periodic_box, 1.2);
+        let (energy, forces) = particle_mesh(&particles, &periodic_box, 64);
+        assert!(
+            (energy - exact).abs() < 1e-3 * exact.abs(),
+            "{} {}",
+            energy,
+            exact
+        );
+        let error: f64 = forces
+            .iter()
+            .zip(&exact_forces)
+            .map(|(a, b)| (a - b).magnitude2())
+            .sum();
+        let total: f64 = exact_forces.iter().map(|f| f.magnitude2()).sum();
+        assert!((error / total).sqrt() < 1e-2, "{}", (error / total).sqrt());
+    }
+}
@@ -0,0 +1,174 @@
+use cgmath::{EuclideanSpace, Zero};
+
+use crate::{
+    dimension::Vector,
+    particle::{ParticleMut, ParticleRef},
+};
+
+/// Uniform electric and magnetic fields, acting on every charge. The electric potential
+/// `-E . r` isn't periodic, so the field only makes sense in open space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExternalField<V: Vector = cgmath::Vector2<f64>> {
+    pub electric: V,
+    /// In 2D just its strength along the z axis, out of the screen.
+    pub magnetic: V::Axial,
+}
+
+impl<V: Vector> Default for ExternalField<V> {
+    fn default() -> Self {
+        Self {
+            electric: V::zero(),
+            magnetic: V::Axial::zero(),
+        }
+    }
+}
+
+impl<V: Vector> ExternalField<V> {
+    pub fn is_zero(&self) -> bool {
+        self.electric.is_zero() && self.magnetic.is_zero()
+    }
+
+    /// The electric force, which only depends on where the particle is.
+    pub fn electric_force(&self, particle: ParticleRef<V>) -> V {
+        self.electric * particle.charge()
+    }
+
+    /// The magnetic part of the Lorentz force, `q v x B`, which does no work.
+    pub fn magnetic_force(&self, particle: ParticleRef<V>) -> V {
+        particle.velocity().cross(self.magnetic * particle.charge())
+    }
+
+    pub fn energy(&self, particle: ParticleRef<V>) -> f64 {
+        -particle.charge() * self.electric.dot(particle.position().to_vec())
+    }
+
+    /// Boris velocity update by `dt` under `acceleration` from every force but the magnetic
+    /// one: half the kick, a rotation by the angle the particle gyrates through, then the other
+    /// half. The rotation keeps the speed exactly, so orbits neither spiral in nor out.
+    pub fn boris_kick(&self, particle: &mut ParticleMut<V>, acceleration: V, dt: f64) {
+        particle.kick(acceleration * (dt / 2.0));
+        let t = self.magnetic * (particle.charge() / particle.mass() * dt / 2.0);
+        if !t.is_zero() {
+            let v = particle.velocity();
+            let v_prime = v + v.cross(t);
+            let s = t * (2.0 / (1.0 + V::axial_magnitude2(t)));
+            particle.set_velocity(v + v_prime.cross(s));
+        }
+        particle.kick(acceleration * (dt / 2.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{integrator::Integrator, particle::Particle, simulation::Simulation};
+    use cgmath::InnerSpace;
+
+    fn magnetized(particles: Vec<Particle>, electric: cgmath::Vector2<f64>) -> Simulation {
+        let mut simulation = Simulation::new(particles, vec![]);
+        simulation.integrator = Integrator::Boris;
+        simulation.force_field.external_field = ExternalField {
+            electric,
+            magnetic: 2.0,
+        };
+        simulation
+    }
+
+    #[test]
+    fn cyclotron_orbits_close_without_drifting() {
+        // gyration frequency q B / m = 1, so the orbit has radius v / 1 = 1.5 around (0, -1.5)
+        let particle = Particle::new(cgmath::point2(0.0, 0.0), cgmath::vec2(1.5, 0.0), 2.0, 1.0);
+        let mut simulation = magnetized(vec![particle], cgmath::vec2(0.0, 0.0));
+        let dt = 0.01;
+        let period = 2.0 * std::f64::consts::PI;
+        let mut worst: f64 = 0.0;
+        for _ in 0..100 * (period / dt).round() as usize {
+            simulation.step(dt);
+            let radius =
+                (simulation.particles.get(0).position() - cgmath::point2(0.0, -1.5)).magnitude();
+            worst = worst.max((radius - 1.5).abs());
+        }
+        assert!((simulation.particles.get(0).velocity().magnitude() - 1.5).abs() < 1e-12);
+        // positions are a half step behind the velocities, which moves the circle by about
+        // v dt / 2, but it never grows or shrinks
+        assert!(worst < 1e-2, "{}", worst);
+    }
+
+    #[test]
+    fn opposite_charges_gyrate_opposite_ways() {
+        let particles = vec![
+            Particle::new(cgmath::point2(0.0, 0.0), cgmath::vec2(1.0, 0.0), 1.0, 1.0),
+            Particle::new(cgmath::point2(0.0, 0.0), cgmath::vec2(1.0, 0.0), 1.0, -1.0),
+        ];
+        let mut simulation = magnetized(particles, cgmath::vec2(0.0, 0.0));
+        simulation.step(0.1);
+        assert!(simulation.particles.get(0).velocity().y < 0.0);
+        assert!(simulation.particles.get(1).velocity().y > 0.0);
+    }
+
+    #[test]
+    fn both_charges_drift_along_e_cross_b() {
+        // the guiding centre drifts at (E x B) / B^2 = (E_y, -E_x) / B whatever the charge
+        let electric = cgmath::vec2(0.0, 0.5);
+        let particles = vec![
+            Particle::new(cgmath::point2(0.0, 0.0), cgmath::vec2(0.0, 0.0), 1.0, 1.0),
+            Particle::new(cgmath::point2(0.0, 0.0), cgmath::vec2(0.0, 0.0), 3.0, -1.0),
+        ];
+        let mut simulation = magnetized(particles, electric);
+        // a whole number of gyrations of both, whose periods are 2 pi m / (|q| B)
+        let time = 60.0 * std::f64::consts::PI;
+        let dt = time / 60000.0;
+        for _ in 0..60000 {
+            simulation.step(dt);
+        }
+        for particle in &simulation.particles {
+            let drift = particle.position().to_vec() / time;
+            assert!(
+                (drift - cgmath::vec2(0.25, 0.0)).magnitude() < 1e-3,
+                "{:?}",
+                drift
+            );
+        }
+    }
+
+    #[test]
+    fn electric_field_conserves_energy() {
+        let particle = Particle::new(cgmath::point2(0.0, 0.0), cgmath::vec2(0.0, 1.0), 1.0, -1.0);
+        let mut simulation = Simulation::new(vec![particle], vec![]);
+        simulation.force_field.external_field.electric = cgmath::vec2(1.0, 0.0);
+        let initial = simulation.kinetic_energy() + simulation.potential_energy();
+        for _ in 0..600 {
+            simulation.step(1.0 / 60.0);
+        }
+        // pulled the opposite way to the field, as a negative charge
+        assert!(simulation.particles.get(0).position().x < -40.0);
+        let energy = simulation.kinetic_energy() + simulation.potential_energy();
+        assert!((energy - initial).abs() < 1e-9);
+    }
+
+    #[test]
+    fn charges_spiral_along_the_field_in_3d() {
+        // gyrating around z with radius 1 while drifting along it at the speed it started with
+        let particle = Particle::new(
+            cgmath::point3(0.0, 0.0, 0.0),
+            cgmath::vec3(1.0, 0.0, 0.5),
+            1.0,
+            1.0,
+        );
+        let mut simulation = Simulation::new(vec![particle], vec![]);
+        simulation.integrator = Integrator::Boris;
+        simulation.force_field.external_field.magnetic = cgmath::vec3(0.0, 0.0, 1.0);
+        let dt = 0.01;
+        let period = 2.0 * std::f64::consts::PI;
+        for _ in 0..(period / dt).round() as usize {
+            simulation.step(dt);
+            let particle = simulation.particles.get(0);
+            let radius =
+                (particle.position().to_vec().truncate() - cgmath::vec2(0.0, -1.0)).magnitude();
+            assert!((radius - 1.0).abs() < 1e-2, "{}", radius);
+        }
+        let particle = simulation.particles.get(0);
+        assert!((particle.velocity().magnitude2() - 1.25).abs() < 1e-12);
+        assert!((particle.position().z - 0.5 * period).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,342 @@
+use cgmath::EuclideanSpace;
+
+use crate::{dimension::Vector, particle::ParticleRef};
+
+/// A potential energy landscape that depends only on where each particle is, pushing it
+/// downhill. Only the optical lattice repeats, so the others are meant for open space or a box
+/// they fit inside.
+#[derive(Debug, Clone)]
+pub enum ExternalPotential<V: Vector = cgmath::Vector2<f64>> {
+    /// `k r^2 / 2` around `center`, pulling everything in like a spring.
+    Harmonic { center: V::Point, stiffness: f64 },
+    /// `-strength m / sqrt(r^2 + softening^2)` around `center`, a heavy body fixed in place that
+    /// pulls in proportion to mass. The softening keeps the force finite at the centre.
+    GravityWell {
+        center: V::Point,
+        strength: f64,
+        softening: f64,
+    },
+    /// `-depth (cos^2(pi x / spacing) + cos^2(pi y / spacing))`, a square grid of wells like the
+    /// one standing laser waves trap cold atoms in, with another `cos^2` along z in 3D. It
+    /// repeats, so boxes a whole number of `spacing`s wide stay periodic.
+    OpticalLattice { depth: f64, spacing: f64 },
+    /// In 3D the map is the same at every height.
+    Map(PotentialMap),
+}
+
+impl<V: Vector> ExternalPotential<V> {
+    pub fn energy(&self, particle: ParticleRef<V>) -> f64 {
+        self.evaluate(particle.position(), particle.mass()).0
+    }
+
+    pub fn force(&self, particle: ParticleRef<V>) -> V {
+        self.evaluate(particle.position(), particle.mass()).1
+    }
+
+    /// The energy a particle of unit mass would have at `position`, for drawing.
+    pub fn sample(&self, position: V::Point) -> f64 {
+        self.evaluate(position, 1.0).0
+    }
+
+    /// Energy and force for a particle of `mass` at `position`.
+    fn evaluate(&self, position: V::Point, mass: f64) -> (f64, V) {
+        match self {
+            ExternalPotential::Harmonic { center, stiffness } => {
+                let r = position - *center;
+                (0.5 * stiffness * r.magnitude2(), -r * *stiffness)
+            }
+            ExternalPotential::GravityWell {
+                center,
+                strength,
+                softening,
+            } => {
+                let r = position - *center;
+                let distance = (r.magnitude2() + softening * softening).sqrt();
+                let energy = -strength * mass / distance;
+                (energy, r * (energy / (distance * distance)))
+            }
+            ExternalPotential::OpticalLattice { depth, spacing } => {
+                let k = std::f64::consts::PI / spacing;
+                let energy = -depth
+                    * (0..V::DIMENSION)
+                        .map(|i| (k * position[i]).cos().powi(2))
+                        .sum::<f64>();
+                let force = V::from_fn(|i| (2.0 * k * position[i]).sin()) * (-depth * k);
+                (energy, force)
+            }
+            ExternalPotential::Map(map) => {
+                let (energy, force) =
+                    map.evaluate(cgmath::Point2::from_vec(position.to_vec().xy()));
+                (energy, V::from_xy(force))
+            }
+        }
+    }
+}
+
+/// A potential given as a grid of samples stretched over a rectangle, interpolated bilinearly
+/// in between. Outside the rectangle it carries on at the value of the nearest edge, so the
+/// force there is zero across the edge.
+#[derive(Debug, Clone)]
+pub struct PotentialMap {
+    /// Row by row from the bottom, `columns` at a time.
+    values: Vec<f64>,
+    columns: usize,
+    rows: usize,
+    min: cgmath::Point2<f64>,
+    max: cgmath::Point2<f64>,
+}
+
+impl PotentialMap {
+    /// Panics unless there are at least two rows and columns of `values`, a whole number of
+    /// rows of them.
+    pub fn new(
+        values: Vec<f64>,
+        columns: usize,
+        min: cgmath::Point2<f64>,
+        max: cgmath::Point2<f64>,
+    ) -> Self {
+        let rows = values.len() / columns;
+        assert!(columns >= 2 && rows >= 2 && rows * columns == values.len());
+        Self {
+            values,
+            columns,
+            rows,
+            min,
+            max,
+        }
+    }
+
+    /// Reads a grayscale image, white being `depth` higher than black. The image is stretched
+    /// over the rectangle from `min` to `max`, with its top row along the top edge. It must be
+    /// at least two pixels wide and two high.
+    pub fn load(
+        path: impl AsRef<std::path::Path>,
+        min: cgmath::Point2<f64>,
+        max: cgmath::Point2<f64>,
+        depth: f64,
+    ) -> image::ImageResult<Self> {
+        let image = image::open(path)?.into_luma16();
+        if image.width() < 2 || image.height() < 2 {
+            return Err(image::ImageError::Parameter(
+                image::error::ParameterError::from_kind(image::error::ParameterErrorKind::Generic(
+                    format!(
+                        "a potential map needs at least 2x2 pixels, not {}x{}",
+                        image.width(),
+                        image.height()
+                    ),
+                )),
+            ));
+        }
+        let columns = image.width() as usize;
+        let values = image
+            .rows()
+            .rev()
+            .flatten()
+            .map(|pixel| pixel.0[0] as f64 / u16::MAX as f64 * depth)
+            .collect();
+        Ok(Self::new(values, columns, min, max))
+    }
+
+    fn evaluate(&self, position: cgmath::Point2<f64>) -> (f64, cgmath::Vector2<f64>) {
+        let cell = cgmath::vec2(
+            (self.max.x - self.min.x) / (self.columns - 1) as f64,
+            (self.max.y - self.min.y) / (self.rows - 1) as f64,
+        );
+        // position in cells, and whether it's inside the map along each axis
+        let locate = |p: f64, min: f64, size: f64, count: usize| {
+            let u = (p - min) / size;
+            let last = (count - 1) as f64;
+            let i = u.floor().max(0.0).min(last - 1.0);
+            (i as usize, u.max(0.0).min(last) - i, u > 0.0 && u < last)
+        };
+        let (i, fx, inside_x) = locate(position.x, self.min.x, cell.x, self.columns);
+        let (j, fy, inside_y) = locate(position.y, self.min.y, cell.y, self.rows);
+        let value = |i: usize, j: usize| self.values[j * self.columns + i];
+        let (v00, v10) = (value(i, j), value(i + 1, j));
+        let (v01, v11) = (value(i, j + 1), value(i + 1, j + 1));
+        let energy = v00 * (1.0 - fx) * (1.0 - fy)
+            + v10 * fx * (1.0 - fy)
+            + v01 * (1.0 - fx) * fy
+            + v11 * fx * fy;
+        let mut force = cgmath::vec2(
+            -((v10 - v00) * (1.0 - fy) + (v11 - v01) * fy) / cell.x,
+            -((v01 - v00) * (1.0 - fx) + (v11 - v10) * fx) / cell.y,
+        );
+        if !inside_x {
+            force.x = 0.0;
+        }
+        if !inside_y {
+            force.y = 0.0;
+        }
+        (energy, force)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::{Particle, ParticleStore};
+    use cgmath::InnerSpace;
+
+    /// A store of just the one particle, which is `get(0)`.
+    fn particle_at(x: f64, y: f64, mass: f64) -> ParticleStore {
+        let particle = Particle::new(cgmath::point2(x, y), cgmath::vec2(0.0, 0.0), mass, 0.0);
+        std::iter::once(particle).collect()
+    }
+
+    fn dip_map() -> PotentialMap {
+        // an uneven dip in the middle of a 5 by 4 map
+        let values = vec![
+            1.0, 1.0, 0.9, 1.0, 1.0, //
+            1.0, 0.4, 0.2, 0.5, 1.0, //
+            0.8, 0.3, 0.0, 0.6, 1.0, //
+            1.0, 1.0, 1.0, 0.7, 1.0,
+        ];
+        PotentialMap::new(
+            values,
+            5,
+            cgmath::point2(-4.0, -3.0),
+            cgmath::point2(4.0, 3.0),
+        )
+    }
+
+    #[test]
+    fn forces_are_minus_the_energy_gradient() {
+        let potentials = vec![
+            ExternalPotential::Harmonic {
+                center: cgmath::point2(1.0, -2.0),
+                stiffness: 3.0,
+            },
+            ExternalPotential::GravityWell {
+                center: cgmath::point2(0.5, 0.5),
+                strength: 4.0,
+                softening: 0.3,
+            },
+            ExternalPotential::OpticalLattice {
+                depth: 2.0,
+                spacing: 3.0,
+            },
+            ExternalPotential::Map(dip_map()),
+        ];
+        let h = 1e-6;
+        for potential in &potentials {
+            for &(x, y) in &[(0.3, 0.7), (-1.3, 2.2), (2.9, -0.4)] {
+                let particle = particle_at(x, y, 2.0);
+                let energy_at = |dx, dy| potential.energy(particle_at(x + dx, y + dy, 2.0).get(0));
+                let gradient = cgmath::vec2(
+                    (energy_at(h, 0.0) - energy_at(-h, 0.0)) / (2.0 * h),
+                    (energy_at(0.0, h) - energy_at(0.0, -h)) / (2.0 * h),
+                );
+                let force = potential.force(particle.get(0));
+                assert!(
+                    (force + gradient).magnitude() < 1e-6,
+                    "{:?} at ({}, {}): {:?} vs {:?}",
+                    potential,
+                    x,
+                    y,
+                    force,
+                    -gradient
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gravity_pulls_in_proportion_to_mass() {
+        let well = ExternalPotential::GravityWell {
+            center: cgmath::point2(0.0, 0.0),
+            strength: 1.0,
+            softening: 0.0,
+        };
+        let light = well.force(particle_at(2.0, 0.0, 1.0).get(0));
+        let heavy = well.force(particle_at(2.0, 0.0, 3.0).get(0));
+        assert!((light - cgmath::vec2(-0.25, 0.0)).magnitude() < 1e-12);
+        assert!((heavy - light * 3.0).magnitude() < 1e-12);
+        assert_eq!(well.sample(cgmath::point2(2.0, 0.0)), -0.5);
+    }
+
+    #[test]
+    fn optical_lattice_wells_sit_on_the_grid() {
+        let lattice = ExternalPotential::OpticalLattice {
+            depth: 1.5,
+            spacing: 2.0,
+        };
+        for &(x, y) in &[(0.0, 0.0), (2.0, -4.0), (-6.0, 8.0)] {
+            assert!((lattice.sample(cgmath::point2(x, y)) + 3.0).abs() < 1e-12);
+            assert!(lattice.force(particle_at(x, y, 1.0).get(0)).magnitude() < 1e-12);
+            // and pulls back towards the well from either side
+            assert!(lattice.force(particle_at(x + 0.3, y, 1.0).get(0)).x < 0.0);
+            assert!(lattice.force(particle_at(x - 0.3, y, 1.0).get(0)).x > 0.0);
+        }
+    }
+
+    #[test]
+    fn map_matches_its_samples_and_is_flat_outside() {
+        let map = ExternalPotential::Map(dip_map());
+        assert_eq!(map.sample(cgmath::point2(0.0, 1.0)), 0.0);
+        assert_eq!(map.sample(cgmath::point2(4.0, 3.0)), 1.0);
+        assert!((map.sample(cgmath::point2(1.0, 1.0)) - 0.3).abs() < 1e-12);
+        // past the left edge the value carries on and nothing pushes along x
+        let outside = particle_at(-6.0, 1.0, 1.0);
+        assert_eq!(map.energy(outside.get(0)), 0.8);
+        assert_eq!(map.force(outside.get(0)).x, 0.0);
+    }
+
+    #[test]
+    fn trapped_particle_oscillates_with_the_trap_period() {
+        // period 2 pi sqrt(m / k) = pi, whatever the amplitude
+        let particle = Particle::new(cgmath::point2(2.0, 0.0), cgmath::vec2(0.0, 0.0), 1.0, 0.0);
+        let mut simulation = crate::simulation::Simulation::new(vec![particle], vec![]);
+        simulation.force_field.external_potentials = vec![ExternalPotential::Harmonic {
+            center: cgmath::point2(0.0, 0.0),
+            stiffness: 4.0,
+        }];
+        let initial = simulation.potential_energy();
+        assert_eq!(initial, 8.0);
+        let steps = 1000;
+        for _ in 0..steps {
+            simulation.step(std::f64::consts::PI / steps as f64);
+        }
+        let particle = simulation.particles.get(0);
+        assert!((particle.position() - cgmath::point2(2.0, 0.0)).magnitude() < 1e-4);
+        let energy = simulation.kinetic_energy() + simulation.potential_energy();
+        assert!((energy - initial).abs() < 1e-4);
+    }
+
+    #[test]
+    fn loads_a_grayscale_image() {
+        // black on the bottom row and white on the top, which should be high
+        let mut image = image::GrayImage::new(3, 2);
+        for x in 0..3 {
+            image.put_pixel(x, 0, image::Luma([255]));
+        }
+        let path = std::env::temp_dir().join(format!("atomica-map-{}.png", std::process::id()));
+        image.save(&path).unwrap();
+        let map = PotentialMap::load(
+            &path,
+            cgmath::point2(0.0, 0.0),
+            cgmath::point2(2.0, 1.0),
+            5.0,
+        );
+        std::fs::remove_file(&path).unwrap();
+        let map = ExternalPotential::Map(map.unwrap());
+        assert_eq!(map.sample(cgmath::point2(1.0, 0.0)), 0.0);
+        assert_eq!(map.sample(cgmath::point2(1.0, 1.0)), 5.0);
+        let force = map.force(particle_at(1.0, 0.5, 1.0).get(0));
+        assert!((force - cgmath::vec2(0.0, -5.0)).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn rejects_an_image_one_pixel_across() {
+        let path = std::env::temp_dir().join(format!("atomica-line-{}.png", std::process::id()));
+        image::GrayImage::new(1, 4).save(&path).unwrap();
+        let map = PotentialMap::load(
+            &path,
+            cgmath::point2(0.0, 0.0),
+            cgmath::point2(2.0, 1.0),
+            5.0,
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(map, Err(image::ImageError::Parameter(_))));
+    }
+}
@@ -0,0 +1,78 @@
+use cgmath::EuclideanSpace;
+use wgpu::{util::DeviceExt, Device};
+
+use crate::{dimension::Vector, external_potential::ExternalPotential, lines::LineVertex};
+
+/// Colors of the lowest and highest energy in view, dim enough for particles to stand out.
+const LOW: [f32; 3] = [0.08, 0.12, 0.3];
+const HIGH: [f32; 3] = [0.42, 0.22, 0.1];
+
+/// External potentials sampled on a grid and drawn as smoothly shaded triangles behind
+/// everything else, using the line shaders.
+pub struct Heatmap {
+    vertexes: Vec<LineVertex>,
+}
+
+impl Heatmap {
+    /// Samples the total energy of a unit mass over the rectangle from `min` to `max`, split into
+    /// `columns` by `rows` cells, which in 3D is the slice through z = 0. Colors span the
+    /// energies in view, so zooming shows detail.
+    pub fn new<V: Vector>(
+        potentials: &[ExternalPotential<V>],
+        min: cgmath::Point2<f64>,
+        max: cgmath::Point2<f64>,
+        columns: usize,
+        rows: usize,
+    ) -> Self {
+        let point = |i: usize, j: usize| {
+            cgmath::point2(
+                min.x + (max.x - min.x) * i as f64 / columns as f64,
+                min.y + (max.y - min.y) * j as f64 / rows as f64,
+            )
+        };
+        let energies: Vec<f64> = (0..=rows)
+            .flat_map(|j| (0..=columns).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let at = V::Point::from_vec(V::from_xy(point(i, j).to_vec()));
+                potentials.iter().map(|p| p.sample(at)).sum()
+            })
+            .collect();
+        let lowest = energies.iter().copied().fold(f64::INFINITY, f64::min);
+        let highest = energies.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let vertex = |i: usize, j: usize| {
+            let energy = energies[j * (columns + 1) + i];
+            let t = if highest > lowest {
+                ((energy - lowest) / (highest - lowest)) as f32
+            } else {
+                0.0
+            };
+            let mut color = LOW;
+            for (c, high) in color.iter_mut().zip(&HIGH) {
+                *c += (high - *c) * t;
+            }
+            let p = point(i, j);
+            LineVertex::new([p.x as f32, p.y as f32, 0.0], color)
+        };
+        let mut vertexes = Vec::with_capacity(6 * columns * rows);
+        for j in 0..rows {
+            for i in 0..columns {
+                for &(di, dj) in &[(0, 0), (1, 0), (1, 1), (0, 0), (1, 1), (0, 1)] {
+                    vertexes.push(vertex(i + di, j + dj));
+                }
+            }
+        }
+        Self { vertexes }
+    }
+
+    pub fn get_buffer(&self, device: &Device) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("heatmap buffer"),
+            contents: bytemuck::cast_slice(&self.vertexes[..]),
+            usage: wgpu::BufferUsages::VERTEX,
+        })
+    }
+
+    pub fn len(&self) -> u32 {
+        self.vertexes.len() as _
+    }
+}
@@ -0,0 +1,334 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+use crate::{dimension::Vector, particle::ParticleStore, potential::ForceField};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integrator {
+    /// Explicit Euler, only here to show how badly it drifts.
+    Euler,
+    SemiImplicitEuler,
+    #[default]
+    VelocityVerlet,
+    /// Drift-kick-drift leapfrog.
+    Leapfrog,
+    RungeKutta4,
+    /// BAOAB Langevin dynamics: velocity Verlet with the velocities relaxed towards the
+    /// solvent temperature by friction and random kicks in the middle of the step.
+    Langevin,
+    /// Leapfrog with the Boris velocity update, which turns charges through the magnetic field
+    /// by rotating their velocities, so cyclotron orbits keep their speed exactly. The other
+    /// integrators treat the magnetic force like any other and slowly spiral.
+    Boris,
+    /// Overdamped Langevin dynamics, where friction is so strong that particles move at the
+    /// drift velocity `force / (mass * friction)` plus diffusion. The velocity is set to the
+    /// drift velocity, so the kinetic energy says nothing about the temperature.
+    Brownian,
+}
+
+/// The solvent the stochastic integrators put particles in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Solvent {
+    /// Rate at which velocities are damped.
+    pub friction: f64,
+    pub temperature: f64,
+}
+
+impl Default for Solvent {
+    fn default() -> Self {
+        Self {
+            friction: 1.0,
+            temperature: 0.5,
+        }
+    }
+}
+
+fn gaussian_vector<V: Vector>(rng: &mut impl Rng) -> V {
+    V::from_fn(|_| rng.sample(StandardNormal))
+}
+
+/// Accelerations from every force, including the magnetic one for the particles' current
+/// velocities.
+pub fn accelerations<V: Vector>(particles: &ParticleStore<V>, field: &mut ForceField<V>) -> Vec<V> {
+    let external_field = field.external_field;
+    field
+        .forces(particles)
+        .into_iter()
+        .zip(particles)
+        .map(|(force, particle)| {
+            (force + external_field.magnetic_force(particle)) / particle.mass()
+        })
+        .collect()
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 8] = [
+        Integrator::Euler,
+        Integrator::SemiImplicitEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Leapfrog,
+        Integrator::RungeKutta4,
+        Integrator::Boris,
+        Integrator::Langevin,
+        Integrator::Brownian,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::Euler => "euler",
+            Integrator::SemiImplicitEuler => "semi-implicit euler",
+            Integrator::VelocityVerlet => "velocity verlet",
+            Integrator::Leapfrog => "leapfrog",
+            Integrator::RungeKutta4 => "rk4",
+            Integrator::Boris => "boris",
+            Integrator::Langevin => "langevin",
+            Integrator::Brownian => "brownian",
+        }
+    }
+
+    /// Whether steps draw on the random number generator, so that two runs of the same step
+    /// only agree if they start from the same seed.
+    pub fn is_stochastic(&self) -> bool {
+        matches!(self, Integrator::Langevin | Integrator::Brownian)
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&i| i == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn step<V: Vector>(
+        &self,
+        particles: &mut ParticleStore<V>,
+        field: &mut ForceField<V>,
+        dt: f64,
+        solvent: &Solvent,
+        rng: &mut impl Rng,
+    ) {
+        match self {
+            Integrator::Euler => {
+                let a = accelerations(particles, field);
+                particles.drift(dt);
+                particles.accelerate(&a, dt);
+            }
+            Integrator::SemiImplicitEuler => {
+                let a = accelerations(particles, field);
+                particles.accelerate(&a, dt);
+                particles.drift(dt);
+            }
+            Integrator::VelocityVerlet => {
+                let a = accelerations(particles, field);
+                particles.accelerate(&a, dt / 2.0);
+                particles.drift(dt);
+                let a = accelerations(particles, field);
+                particles.accelerate(&a, dt / 2.0);
+            }
+            Integrator::Leapfrog => {
+                particles.drift(dt / 2.0);
+                let a = accelerations(particles, field);
+                particles.accelerate(&a, dt);
+                particles.drift(dt / 2.0);
+            }
+            Integrator::RungeKutta4 => Self::runge_kutta_4(particles, field, dt),
+            Integrator::Boris => {
+                let forces = field.forces(particles);
+                let external_field = field.external_field;
+                for (i, force) in forces.into_iter().enumerate() {
+                    let mut particle = particles.get_mut(i);
+                    let a = force / particle.mass();
+                    external_field.boris_kick(&mut particle, a, dt);
+                    particle.drift(particle.velocity() * dt);
+                }
+            }
+            Integrator::Langevin => {
+                let decay = (-solvent.friction * dt).exp();
+                let a = accelerations(particles, field);
+                for (i, a) in a.into_iter().enumerate() {
+                    let mut particle = particles.get_mut(i);
+                    particle.kick(a * (dt / 2.0));
+                    particle.drift(particle.velocity() * (dt / 2.0));
+                    let spread =
+                        ((1.0 - decay * decay) * solvent.temperature / particle.mass()).sqrt();
+                    let velocity = particle.velocity() * decay + gaussian_vector::<V>(rng) * spread;
+                    particle.set_velocity(velocity);
+                    particle.drift(velocity * (dt / 2.0));
+                }
+                let a = accelerations(particles, field);
+                particles.accelerate(&a, dt / 2.0);
+            }
+            Integrator::Brownian => {
+                let a = accelerations(particles, field);
+                for (i, a) in a.into_iter().enumerate() {
+                    let mut particle = particles.get_mut(i);
+                    let drift = a / solvent.friction;
+                    let diffusion = solvent.temperature / (particle.mass() * solvent.friction);
+                    particle.set_velocity(drift);
+                    particle.drift(
+                        drift * dt + gaussian_vector::<V>(rng) * (2.0 * diffusion * dt).sqrt(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn runge_kutta_4<V: Vector>(
+        particles: &mut ParticleStore<V>,
+        field: &mut ForceField<V>,
+        dt: f64,
+    ) {
+        // each stage is (dx/dt, dv/dt) for every particle, evaluated at the state offset from
+        // the start of the step by the previous stage
+        let mut stages: Vec<Vec<(V, V)>> = vec![];
+        for &h in &[0.0, dt / 2.0, dt / 2.0, dt] {
+            let mut probe = particles.clone();
+            if let Some(previous) = stages.last() {
+                probe.move_all(|i, position| position + previous[i].0 * h);
+                for (velocity, &(_, dv)) in probe.velocities_mut().iter_mut().zip(previous) {
+                    *velocity += dv * h;
+                }
+            }
+            let a = accelerations(&probe, field);
+            stages.push(probe.velocities().iter().copied().zip(a).collect());
+        }
+        let steps: Vec<(V, V)> = (0..particles.len())
+            .map(|i| {
+                [1.0, 2.0, 2.0, 1.0]
+                    .iter()
+                    .zip(&stages)
+                    .map(|(weight, stage)| (stage[i].0 * *weight, stage[i].1 * *weight))
+                    .fold((V::zero(), V::zero()), |acc, d| (acc.0 + d.0, acc.1 + d.1))
+            })
+            .collect();
+        particles.move_all(|i, position| position + steps[i].0 * (dt / 6.0));
+        for (velocity, &(_, dv)) in particles.velocities_mut().iter_mut().zip(&steps) {
+            *velocity += dv * (dt / 6.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use crate::{potential, scene, simulation::Simulation};
+    use cgmath::{EuclideanSpace, InnerSpace};
+
+    fn energy_drift(integrator: Integrator) -> f64 {
+        let mut simulation = Simulation::new(
+            scene::colliding_pair().particles,
+            vec![
+                Box::new(potential::Coulomb::default()),
+                Box::new(potential::LennardJones::default()),
+            ],
+        );
+        simulation.integrator = integrator;
+        let initial = simulation.kinetic_energy() + simulation.potential_energy();
+        for _ in 0..10_000 {
+            simulation.step(1.0 / 60.0);
+        }
+        let energy = simulation.kinetic_energy() + simulation.potential_energy();
+        (energy - initial).abs() / initial.abs()
+    }
+
+    #[test]
+    fn symplectic_integrators_conserve_energy() {
+        for &integrator in &[Integrator::VelocityVerlet, Integrator::Leapfrog] {
+            let drift = energy_drift(integrator);
+            // the pair swings through the whole Lennard-Jones well, out to 2.5 mixed sigmas
+            assert!(drift < 1e-3, "{} drifted by {}", integrator.name(), drift);
+        }
+    }
+
+    #[test]
+    fn runge_kutta_conserves_energy() {
+        let drift = energy_drift(Integrator::RungeKutta4);
+        assert!(drift < 1e-3, "rk4 drifted by {}", drift);
+    }
+
+    #[test]
+    fn euler_drifts_more_than_verlet() {
+        assert!(energy_drift(Integrator::Euler) > 100.0 * energy_drift(Integrator::VelocityVerlet));
+    }
+
+    /// Free particles in a solvent, all starting at the origin.
+    fn solution(integrator: Integrator) -> Simulation {
+        let particles = (0..400)
+            .map(|i| {
+                Particle::new(
+                    cgmath::point2(0.0, 0.0),
+                    cgmath::vec2(0.0, 0.0),
+                    1.0 + (i % 4) as f64,
+                    0.0,
+                )
+            })
+            .collect();
+        let mut simulation = Simulation::new(particles, vec![]);
+        simulation.integrator = integrator;
+        simulation.solvent = Solvent {
+            friction: 2.0,
+            temperature: 0.8,
+        };
+        simulation
+    }
+
+    #[test]
+    fn langevin_equilibrates_to_the_solvent_temperature() {
+        let mut simulation = solution(Integrator::Langevin);
+        let mut sum = 0.0;
+        for step in 0..2000 {
+            simulation.step(0.02);
+            if step >= 500 {
+                sum += simulation.temperature();
+            }
+        }
+        let average = sum / 1500.0;
+        assert!((average - 0.8).abs() < 0.03, "{}", average);
+    }
+
+    #[test]
+    fn brownian_particles_diffuse() {
+        let mut simulation = solution(Integrator::Brownian);
+        let (steps, dt) = (100, 0.01);
+        for _ in 0..steps {
+            simulation.step(dt);
+        }
+        // <r^2> = 4 D t in two dimensions, with D = T / (m * friction)
+        let relative: f64 = simulation
+            .particles
+            .iter()
+            .map(|p| {
+                let diffusion = 0.8 / (p.mass() * 2.0);
+                p.position().to_vec().magnitude2() / (4.0 * diffusion * steps as f64 * dt)
+            })
+            .sum::<f64>()
+            / simulation.particles.len() as f64;
+        assert!((relative - 1.0).abs() < 0.1, "{}", relative);
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let run = |seed| {
+            let mut simulation = solution(Integrator::Langevin);
+            simulation.seed(seed);
+            for _ in 0..50 {
+                simulation.step(0.02);
+            }
+            simulation
+                .particles
+                .iter()
+                .map(|p| p.position())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+    }
+
+    #[test]
+    fn next_cycles_through_all() {
+        let mut integrator = Integrator::default();
+        for _ in 0..Integrator::ALL.len() {
+            integrator = integrator.next();
+        }
+        assert_eq!(integrator, Integrator::default());
+    }
+}
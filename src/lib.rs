@@ -0,0 +1,21 @@
+pub mod barnes_hut;
+pub mod barostat;
+pub mod boundary;
+pub mod collision;
+pub mod compute;
+pub mod dimension;
+pub mod ewald;
+pub mod external_field;
+pub mod external_potential;
+pub mod integrator;
+pub mod neighbor_list;
+pub mod particle;
+pub mod particle_trail;
+pub mod potential;
+pub mod reaction;
+pub mod scene;
+pub mod simulation;
+pub mod species;
+pub mod thermostat;
+pub mod topology;
+pub mod wall;
@@ -0,0 +1,9 @@
+#version 440 core
+
+layout(location = 0) in vec3 line_color;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    out_color = vec4(line_color, 1);
+}
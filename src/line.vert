@@ -0,0 +1,16 @@
+#version 440 core
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 color;
+
+layout(location = 0) out vec3 line_color;
+
+layout(std140, set = 0, binding = 0) uniform transform {
+    mat4 view;
+    mat4 projection;
+};
+
+void main() {
+    gl_Position = projection * view * vec4(position, 1);
+    line_color = color * color;
+}
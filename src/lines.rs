@@ -0,0 +1,79 @@
+use std::marker::PhantomData;
+
+use cgmath::EuclideanSpace;
+use wgpu::{util::DeviceExt, Device};
+
+use crate::dimension::Vector;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LineVertex {
+    /// With z left at 0 in 2D.
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for LineVertex {}
+unsafe impl bytemuck::Pod for LineVertex {}
+
+impl LineVertex {
+    pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self { position, color }
+    }
+}
+
+/// A batch of line segments drawn as a line list, for box outlines, walls and bonds.
+pub struct Lines<V: Vector = cgmath::Vector2<f64>> {
+    vertexes: Vec<LineVertex>,
+    dimension: PhantomData<V>,
+}
+
+impl<V: Vector> Lines<V> {
+    pub fn new() -> Self {
+        Self {
+            vertexes: vec![],
+            dimension: PhantomData,
+        }
+    }
+
+    pub fn push(&mut self, a: V::Point, b: V::Point, color: [f32; 3]) {
+        for &p in [a, b].iter() {
+            self.vertexes
+                .push(LineVertex::new(V::render_position(p), color));
+        }
+    }
+
+    /// The edges of the box from `min` to `max`, a rectangle in 2D and a cuboid in 3D.
+    pub fn push_box(&mut self, min: V::Point, max: V::Point, color: [f32; 3]) {
+        // corners are numbered by which axes they're at the max end of
+        let corner = |bits: usize| {
+            V::Point::from_vec(V::from_fn(
+                |i| {
+                    if bits >> i & 1 == 0 {
+                        min[i]
+                    } else {
+                        max[i]
+                    }
+                },
+            ))
+        };
+        for bits in 0..1 << V::DIMENSION {
+            for axis in 0..V::DIMENSION {
+                if bits >> axis & 1 == 0 {
+                    self.push(corner(bits), corner(bits | 1 << axis), color);
+                }
+            }
+        }
+    }
+    pub fn get_buffer(&self, device: &Device) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("line buffer"),
+            contents: bytemuck::cast_slice(&self.vertexes[..]),
+            usage: wgpu::BufferUsages::VERTEX,
+        })
+    }
+
+    pub fn len(&self) -> u32 {
+        self.vertexes.len() as _
+    }
+}
@@ -6,9 +6,14 @@ use futures::executor::block_on;
 use sdl2::event::Event;
 use wgpu::util::DeviceExt;
 
+use atomica::{
+    barostat, boundary, compute, dimension, ewald, external_potential, particle, particle_trail,
+    potential, reaction, scene, simulation, species, thermostat, wall,
+};
+
 mod camera;
-mod particle;
-mod particle_trail;
+mod heatmap;
+mod lines;
 
 fn string_err(s: String) -> StrErr {
     StrErr { s }
@@ -69,6 +74,29 @@ fn and_a_square_too(device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer, u32)
     (vertex_buffer, index_buffer, 6)
 }
 
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+fn create_depth_view(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
 fn correct_pos(
     (x, y): (f32, f32),
     config: &wgpu::SurfaceConfiguration,
@@ -96,6 +124,133 @@ fn correct_rel(
     cgmath::vec2(v.x, v.y)
 }
 
+/// Seconds a cross marks where a bond formed or broke.
+const BOND_FLASH_TIME: f64 = 0.5;
+
+/// Ewald split for the periodic Coulomb models, sized for boxes of about 20 across.
+const PERIODIC_SPLIT: ewald::EwaldSplit = ewald::EwaldSplit {
+    alpha: 0.8,
+    real_cutoff: 4.0,
+};
+
+/// A named set of pair potentials, which M cycles through.
+type PhysicsModel<V> = (&'static str, Vec<Box<dyn potential::PairPotential<V>>>);
+
+/// Ewald and PPPM only exist in 2D, so in 3D those models sum Coulomb directly instead.
+fn physics_models<V: dimension::Vector>() -> Vec<PhysicsModel<V>> {
+    vec![
+        (
+            "coulomb + lennard-jones",
+            vec![
+                Box::new(potential::Coulomb::default()),
+                Box::new(potential::LennardJones::default()),
+            ],
+        ),
+        (
+            "barnes-hut coulomb + lennard-jones",
+            vec![
+                Box::new(potential::Coulomb {
+                    summation: potential::Summation::BarnesHut { theta: 0.5 },
+                    ..potential::Coulomb::default()
+                }),
+                Box::new(potential::LennardJones::default()),
+            ],
+        ),
+        (
+            "ewald coulomb + lennard-jones",
+            vec![
+                Box::new(potential::Coulomb {
+                    summation: potential::Summation::Ewald {
+                        split: PERIODIC_SPLIT,
+                        k_max: 16,
+                    },
+                    ..potential::Coulomb::default()
+                }),
+                Box::new(potential::LennardJones::default()),
+            ],
+        ),
+        (
+            "pppm coulomb + lennard-jones",
+            vec![
+                Box::new(potential::Coulomb {
+                    summation: potential::Summation::ParticleMesh {
+                        split: PERIODIC_SPLIT,
+                        mesh: 32,
+                    },
+                    ..potential::Coulomb::default()
+                }),
+                Box::new(potential::LennardJones::default()),
+            ],
+        ),
+        (
+            "coulomb + soft spheres",
+            vec![
+                Box::new(potential::Coulomb::default()),
+                Box::new(potential::SoftSphere::default()),
+            ],
+        ),
+        (
+            // for gravity on its own, with just enough repulsion to keep bodies apart
+            "soft spheres",
+            vec![Box::new(potential::SoftSphere::default())],
+        ),
+        (
+            "screened coulomb + lennard-jones",
+            vec![
+                Box::new(potential::Yukawa {
+                    k: 1.0,
+                    screening_length: 3.0,
+                    cutoff: Some(15.0),
+                }),
+                Box::new(potential::LennardJones::default()),
+            ],
+        ),
+        (
+            "morse",
+            vec![Box::new(potential::Morse {
+                depth: 1.0,
+                width: 1.5,
+                equilibrium: 1.0,
+                cutoff: Some(5.0),
+            })],
+        ),
+        (
+            "springs",
+            vec![Box::new(potential::HarmonicSpring {
+                stiffness: 0.1,
+                rest_length: 2.0,
+                cutoff: None,
+            })],
+        ),
+    ]
+}
+
+/// Everything on the GPU that outlives switching between 2D and 3D.
+struct Renderer {
+    window: sdl2::video::Window,
+    surface: wgpu::Surface,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface_config: wgpu::SurfaceConfiguration,
+    depth_view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    main_circle_pipeline: wgpu::RenderPipeline,
+    gpu_circle_pipeline: wgpu::RenderPipeline,
+    sphere_pipeline: wgpu::RenderPipeline,
+    gpu_sphere_pipeline: wgpu::RenderPipeline,
+    trail_pipeline: wgpu::RenderPipeline,
+    line_pipeline: wgpu::RenderPipeline,
+    heatmap_pipeline: wgpu::RenderPipeline,
+    circle: (wgpu::Buffer, wgpu::Buffer, u32),
+    square: (wgpu::Buffer, wgpu::Buffer, u32),
+}
+
+/// Why `run` stopped.
+enum Outcome {
+    Quit,
+    SwitchDimension,
+}
+
 fn main() -> color_eyre::Result<()> {
     env_logger::init();
     let sdl_context = sdl2::init().map_err(string_err)?;
@@ -129,22 +284,38 @@ fn main() -> color_eyre::Result<()> {
         device.create_shader_module(&wgpu::include_spirv!("main_circle.vert.spirv"));
     let main_circle_frag =
         device.create_shader_module(&wgpu::include_spirv!("main_circle.frag.spirv"));
+    let sphere_vert = device.create_shader_module(&wgpu::include_spirv!("sphere.vert.spirv"));
+    let sphere_frag = device.create_shader_module(&wgpu::include_spirv!("sphere.frag.spirv"));
     let trail_vert = device.create_shader_module(&wgpu::include_spirv!("trail.vert.spirv"));
     let trail_frag = device.create_shader_module(&wgpu::include_spirv!("trail.frag.spirv"));
+    let line_vert = device.create_shader_module(&wgpu::include_spirv!("line.vert.spirv"));
+    let line_frag = device.create_shader_module(&wgpu::include_spirv!("line.frag.spirv"));
     //insert shader stuff here
 
     let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: Some("transform uniform layout"),
-        entries: &[wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::VERTEX,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: None,
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
             },
-            count: None,
-        }],
+        ],
     });
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("pipeline layout"),
@@ -177,37 +348,105 @@ fn main() -> color_eyre::Result<()> {
         }),
         write_mask: wgpu::ColorWrites::ALL,
     }];
+    // everything in 2D sits at the same depth, so only the 3D pipelines really get tested
+    let depth_stencil = |depth_write_enabled, depth_compare| {
+        Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled,
+            depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        })
+    };
 
-    let main_circle_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("main circle pipeline"),
-        layout: Some(&pipeline_layout),
-        vertex: wgpu::VertexState {
-            module: &main_circle_vert,
-            entry_point: "main",
-            buffers: &[wgpu::VertexBufferLayout {
-                array_stride: std::mem::size_of::<[f32; 2]>() as u64,
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &wgpu::vertex_attr_array![0 => Float32x2],
-            },
-            wgpu::VertexBufferLayout {
-                array_stride: std::mem::size_of::<particle::RawParticle>() as u64,
-                step_mode: wgpu::VertexStepMode::Instance,
-                attributes: &wgpu::vertex_attr_array![1 => Float32x2, 2 => Float32, 3 => Float32],
-            }],
+    // one of each for particles uploaded every frame, a column at a time straight out of the
+    // simulation's `ParticleStore`, and for the ones the GPU simulates itself
+    let cpu_instances = [
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 3]>() as u64,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![1 => Float32x3],
         },
-        fragment: Some(wgpu::FragmentState {
-            module: &main_circle_frag,
-            entry_point: "main",
-            targets: &common_targets,
-        }),
-        primitive: common_primitive,
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState {
-            count: 1,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<f32>() as u64,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![2 => Float32],
         },
-    });
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<u32>() as u64,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![3 => Uint32],
+        },
+    ];
+    let gpu_instances = [wgpu::VertexBufferLayout {
+        array_stride: compute::INSTANCE_STRIDE,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &compute::INSTANCE_ATTRIBUTES,
+    }];
+    let particle_pipeline = |label,
+                             vertex_module,
+                             fragment_module,
+                             instances: &[wgpu::VertexBufferLayout],
+                             depth_stencil| {
+        let mut buffers = vec![wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 2]>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+        }];
+        buffers.extend_from_slice(instances);
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: "main",
+                buffers: &buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: fragment_module,
+                entry_point: "main",
+                targets: &common_targets,
+            }),
+            primitive: common_primitive,
+            depth_stencil,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        })
+    };
+    let circle_depth = || depth_stencil(false, wgpu::CompareFunction::Always);
+    let main_circle_pipeline = particle_pipeline(
+        "main circle pipeline",
+        &main_circle_vert,
+        &main_circle_frag,
+        &cpu_instances,
+        circle_depth(),
+    );
+    let gpu_circle_pipeline = particle_pipeline(
+        "gpu circle pipeline",
+        &main_circle_vert,
+        &main_circle_frag,
+        &gpu_instances,
+        circle_depth(),
+    );
+    // camera-facing squares, with the fragment shader ray tracing the sphere inside each
+    let sphere_depth = || depth_stencil(true, wgpu::CompareFunction::Less);
+    let sphere_pipeline = particle_pipeline(
+        "sphere pipeline",
+        &sphere_vert,
+        &sphere_frag,
+        &cpu_instances,
+        sphere_depth(),
+    );
+    let gpu_sphere_pipeline = particle_pipeline(
+        "gpu sphere pipeline",
+        &sphere_vert,
+        &sphere_frag,
+        &gpu_instances,
+        sphere_depth(),
+    );
 
     let trail_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("trail pipeline"),
@@ -224,7 +463,7 @@ fn main() -> color_eyre::Result<()> {
                 wgpu::VertexBufferLayout {
                     array_stride: std::mem::size_of::<particle_trail::RawTrail>() as u64,
                     step_mode: wgpu::VertexStepMode::Instance,
-                    attributes: &wgpu::vertex_attr_array![1 => Float32x2, 2 => Float32, 3 => Float32, 4 => Float32],
+                    attributes: &wgpu::vertex_attr_array![1 => Float32x3, 2 => Float32, 3 => Float32, 4 => Uint32],
                 },
             ],
         },
@@ -234,7 +473,64 @@ fn main() -> color_eyre::Result<()> {
             targets: &common_targets
         }),
         primitive: common_primitive,
-        depth_stencil: None,
+        // hidden behind particles, but see-through, so they don't hide each other
+        depth_stencil: depth_stencil(false, wgpu::CompareFunction::LessEqual),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    });
+
+    let line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("line pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &line_vert,
+            entry_point: "main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<lines::LineVertex>() as u64,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &line_frag,
+            entry_point: "main",
+            targets: &common_targets,
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..common_primitive
+        },
+        depth_stencil: depth_stencil(true, wgpu::CompareFunction::LessEqual),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    });
+
+    // the line shaders again, filling triangles instead
+    let heatmap_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("heatmap pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &line_vert,
+            entry_point: "main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<lines::LineVertex>() as u64,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &line_frag,
+            entry_point: "main",
+            targets: &common_targets,
+        }),
+        primitive: common_primitive,
+        depth_stencil: depth_stencil(false, wgpu::CompareFunction::Always),
         multisample: wgpu::MultisampleState {
             count: 1,
             mask: !0,
@@ -243,7 +539,7 @@ fn main() -> color_eyre::Result<()> {
     });
 
     let (width, height) = window.size();
-    let mut surface_config = wgpu::SurfaceConfiguration {
+    let surface_config = wgpu::SurfaceConfiguration {
         usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
         format: preferred_format,
         width,
@@ -251,26 +547,123 @@ fn main() -> color_eyre::Result<()> {
         present_mode: wgpu::PresentMode::Mailbox,
     };
     surface.configure(&device, &surface_config);
+    let depth_view = create_depth_view(&device, &surface_config);
 
-    let (circle_vertexes, circle_indexes, circle_index_count) = create_a_damn_circle(&device);
-    let (square_vertexes, square_indexes, square_index_count) = and_a_square_too(&device);
+    let circle = create_a_damn_circle(&device);
+    let square = and_a_square_too(&device);
 
-    let mut particles = vec![
-        particle::Particle::new(
-            cgmath::Point2::new(-6.0, 0.75),
-            cgmath::Vector2::new(1.0, 0.0),
-            1.0,
-            1.0,
-        ),
-        particle::Particle::new(
-            cgmath::Point2::new(6.0, -0.75),
-            cgmath::Vector2::new(-1.0, 0.0),
-            1.0,
-            -1.0,
+    let mut renderer = Renderer {
+        window,
+        surface,
+        device,
+        queue,
+        surface_config,
+        depth_view,
+        bind_group_layout,
+        main_circle_pipeline,
+        gpu_circle_pipeline,
+        sphere_pipeline,
+        gpu_sphere_pipeline,
+        trail_pipeline,
+        line_pipeline,
+        heatmap_pipeline,
+        circle,
+        square,
+    };
+
+    // set ATOMICA_POTENTIAL_MAP to a grayscale image to explore its landscape, darker is lower
+    let potential_map = match std::env::var_os("ATOMICA_POTENTIAL_MAP") {
+        Some(path) => Some(
+            external_potential::PotentialMap::load(
+                &path,
+                scene::LANDSCAPE_MIN,
+                scene::LANDSCAPE_MAX,
+                scene::LANDSCAPE_DEPTH,
+            )
+            .with_context(|| format!("failed to load potential map {:?}", path))?,
         ),
-    ];
+        None => None,
+    };
+    // set ATOMICA_SEED to get a different, but still reproducible, run
+    let seed = std::env::var("ATOMICA_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0);
+
+    let mut sdl_pump = sdl_context.event_pump().map_err(string_err)?;
+    // D switches between the 2D and 3D scenes
+    let mut three_dimensional = false;
+    loop {
+        let outcome = if three_dimensional {
+            run::<cgmath::Vector3<f64>>(&mut renderer, &mut sdl_pump, potential_map.as_ref(), seed)?
+        } else {
+            run::<cgmath::Vector2<f64>>(&mut renderer, &mut sdl_pump, potential_map.as_ref(), seed)?
+        };
+        match outcome {
+            Outcome::Quit => break,
+            Outcome::SwitchDimension => three_dimensional = !three_dimensional,
+        }
+    }
+    println!("Hello, world!");
+    Ok(())
+}
+
+/// Runs the scenes of one dimension until the window closes or D asks for the other one. 2D
+/// scenes are drawn as flat circles from above, 3D ones as shaded spheres seen through an
+/// orbiting camera.
+fn run<V: scene::Catalog>(
+    renderer: &mut Renderer,
+    sdl_pump: &mut sdl2::EventPump,
+    potential_map: Option<&external_potential::PotentialMap>,
+    seed: u64,
+) -> color_eyre::Result<Outcome> {
+    let three_dimensional = V::DIMENSION == 3;
+    let mut model_index = 0;
+    let (mut model_name, potentials) = physics_models().swap_remove(model_index);
+    let mut scene_index = 0;
+    let scene::Scene {
+        name: mut scene_name,
+        species: mut species_table,
+        particles,
+        periodic_box,
+        walls,
+        topology,
+        collisions,
+        reactions,
+        external_field,
+        external_potentials,
+        gravity,
+    } = V::scenes(potential_map).swap_remove(scene_index);
+    let mut simulation = simulation::Simulation::new(particles, potentials);
+    simulation.force_field.parallel = true;
+    simulation.force_field.periodic_box = periodic_box;
+    simulation.force_field.topology = topology;
+    simulation.force_field.external_field = external_field;
+    simulation.force_field.external_potentials = external_potentials;
+    simulation.force_field.gravity = gravity;
+    // what the V key turns back on, the scene's own gravity if it has any
+    let mut scene_gravity = gravity.unwrap_or(potential::Gravity {
+        summation: potential::Summation::BarnesHut { theta: 0.5 },
+        ..potential::Gravity::default()
+    });
+    simulation.collisions = collisions;
+    simulation.reactions = reactions;
+    simulation.walls = walls;
+    simulation.step_control = simulation::StepControl::default();
+    simulation.seed(seed);
 
     let mut trails = particle_trail::TrailManager::new();
+    // wall pressure, averaged over about a second at a time
+    let mut wall_pressure = 0.0;
+    let mut pressure_time = 0.0;
+    let mut target_temperature = 0.5;
+    let mut target_pressure = 1.0;
+    let mut display_scale = species::DisplayScale::Physical;
+    let mut show_heatmap = true;
+    // bonds that recently formed or broke, with how many seconds each has left to flash
+    let mut bond_flashes: Vec<(reaction::BondEvent<V>, f64)> = vec![];
+    // C hands the particles over to the GPU to simulate, and C again brings them back
+    let mut gpu_simulation: Option<compute::GpuSimulation> = None;
 
     #[rustfmt::skip]
     let opengl_to_wgpu = cgmath::Matrix4::<f32>::new(
@@ -280,6 +673,12 @@ fn main() -> color_eyre::Result<()> {
         0.0, 0.0, 0.5, 1.0
     );
 
+    let (width, height) = (
+        renderer.surface_config.width,
+        renderer.surface_config.height,
+    );
+    // the -1 factors line up with the +1 ones beside them
+    #[allow(clippy::neg_multiply)]
     let mut projection_matrix = opengl_to_wgpu
         * cgmath::ortho(
             -1.0,
@@ -291,12 +690,13 @@ fn main() -> color_eyre::Result<()> {
         );
 
     let mut camera = camera::Camera::new();
+    let mut orbit_camera = camera::OrbitCamera::new();
 
     let mut accumulated_time = std::time::Duration::ZERO;
     let mut last_frame = std::time::Instant::now();
 
-    let mut sdl_pump = sdl_context.event_pump().map_err(string_err)?;
-    'game_loop: loop {
+    loop {
+        let mut toggle_gpu = false;
         for event in sdl_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
@@ -304,16 +704,186 @@ fn main() -> color_eyre::Result<()> {
                     keycode: Some(sdl2::keyboard::Keycode::Escape),
                     ..
                 } => {
-                    break 'game_loop;
+                    return Ok(Outcome::Quit);
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::D),
+                    ..
+                } => {
+                    return Ok(Outcome::SwitchDimension);
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::P),
+                    ..
+                } => {
+                    orbit_camera.toggle_projection();
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::C),
+                    ..
+                } => {
+                    toggle_gpu = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::M),
+                    ..
+                } => {
+                    let mut models = physics_models();
+                    model_index = (model_index + 1) % models.len();
+                    let (name, potentials) = models.swap_remove(model_index);
+                    model_name = name;
+                    simulation.force_field.potentials = potentials;
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::N),
+                    ..
+                } => {
+                    let mut scenes = V::scenes(potential_map);
+                    scene_index = (scene_index + 1) % scenes.len();
+                    let scene = scenes.swap_remove(scene_index);
+                    scene_name = scene.name;
+                    species_table = scene.species;
+                    let (_, potentials) = physics_models().swap_remove(model_index);
+                    let mut next = simulation::Simulation::new(scene.particles, potentials);
+                    next.force_field.parallel = true;
+                    next.force_field.periodic_box = scene.periodic_box;
+                    next.force_field.topology = scene.topology;
+                    next.force_field.external_field = scene.external_field;
+                    next.force_field.external_potentials = scene.external_potentials;
+                    next.force_field.gravity = scene.gravity;
+                    scene_gravity = scene.gravity.unwrap_or(scene_gravity);
+                    next.collisions = scene.collisions;
+                    next.reactions = scene.reactions;
+                    next.walls = scene.walls;
+                    next.integrator = simulation.integrator;
+                    next.step_control = simulation.step_control;
+                    next.thermostat = simulation.thermostat.take();
+                    next.barostat = simulation.barostat.take();
+                    next.solvent = simulation.solvent;
+                    next.seed(seed);
+                    simulation = next;
+                    trails = particle_trail::TrailManager::new();
+                    bond_flashes.clear();
+                    gpu_simulation = None;
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::I),
+                    ..
+                } => {
+                    simulation.integrator = simulation.integrator.next();
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::T),
+                    ..
+                } => {
+                    simulation.step_control = match simulation.step_control {
+                        simulation::StepControl::Fixed => simulation::StepControl::default(),
+                        simulation::StepControl::Adaptive { .. } => simulation::StepControl::Fixed,
+                    };
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::R),
+                    ..
+                } => {
+                    let scales = species::DisplayScale::ALL;
+                    let current = scales.iter().position(|&s| s == display_scale);
+                    display_scale = scales[current.map_or(0, |i| (i + 1) % scales.len())];
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::G),
+                    ..
+                } => {
+                    show_heatmap = !show_heatmap;
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::V),
+                    ..
+                } => {
+                    simulation.force_field.gravity = match simulation.force_field.gravity {
+                        Some(_) => None,
+                        None => Some(scene_gravity),
+                    };
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::H),
+                    ..
+                } => {
+                    // off, then each thermostat in turn
+                    let kinds = thermostat::ThermostatKind::ALL;
+                    let next = match &simulation.thermostat {
+                        None => Some(0),
+                        Some(current) => kinds
+                            .iter()
+                            .position(|&kind| kind == current.kind)
+                            .map(|i| i + 1)
+                            .filter(|&i| i < kinds.len()),
+                    };
+                    simulation.thermostat = next
+                        .map(|i| thermostat::Thermostat::new(kinds[i], target_temperature, 0.5));
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode @ sdl2::keyboard::Keycode::Up),
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(keycode @ sdl2::keyboard::Keycode::Down),
+                    ..
+                } => {
+                    if keycode == sdl2::keyboard::Keycode::Up {
+                        target_temperature *= 1.25;
+                    } else {
+                        target_temperature /= 1.25;
+                    }
+                    if let Some(thermostat) = &mut simulation.thermostat {
+                        thermostat.target = target_temperature;
+                    }
+                    simulation.solvent.temperature = target_temperature;
+                }
+                Event::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::B),
+                    ..
+                } => {
+                    // off, then each barostat in turn
+                    let kinds = barostat::BarostatKind::ALL;
+                    let next = match &simulation.barostat {
+                        None => Some(0),
+                        Some(current) => kinds
+                            .iter()
+                            .position(|&kind| kind == current.kind)
+                            .map(|i| i + 1)
+                            .filter(|&i| i < kinds.len()),
+                    };
+                    simulation.barostat =
+                        next.map(|i| barostat::Barostat::new(kinds[i], target_pressure, 2.0));
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode @ sdl2::keyboard::Keycode::PageUp),
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(keycode @ sdl2::keyboard::Keycode::PageDown),
+                    ..
+                } => {
+                    if keycode == sdl2::keyboard::Keycode::PageUp {
+                        target_pressure *= 1.25;
+                    } else {
+                        target_pressure /= 1.25;
+                    }
+                    if let Some(barostat) = &mut simulation.barostat {
+                        barostat.target = target_pressure;
+                    }
                 }
+                #[allow(clippy::neg_multiply)]
                 Event::Window {
                     window_id,
                     win_event: sdl2::event::WindowEvent::SizeChanged(width, height),
                     ..
-                } if window_id == window.id() => {
+                } if window_id == renderer.window.id() => {
+                    let surface_config = &mut renderer.surface_config;
                     surface_config.width = width as u32;
                     surface_config.height = height as u32;
-                    surface.configure(&device, &surface_config);
+                    renderer.surface.configure(&renderer.device, surface_config);
+                    renderer.depth_view = create_depth_view(&renderer.device, surface_config);
                     projection_matrix = opengl_to_wgpu
                         * cgmath::ortho(
                             -1.0,
@@ -324,11 +894,17 @@ fn main() -> color_eyre::Result<()> {
                             1.0,
                         );
                 }
-                Event::MouseButtonDown { which: 0, x, y, .. } => camera.click_mouse(correct_pos(
-                    (x as _, -y as _),
-                    &surface_config,
-                    projection_matrix,
-                )),
+                Event::MouseButtonDown { which: 0, x, y, .. } => {
+                    if three_dimensional {
+                        orbit_camera.click_mouse();
+                    } else {
+                        camera.click_mouse(correct_pos(
+                            (x as _, -y as _),
+                            &renderer.surface_config,
+                            projection_matrix,
+                        ));
+                    }
+                }
                 Event::MouseMotion {
                     which: 0,
                     x,
@@ -336,60 +912,327 @@ fn main() -> color_eyre::Result<()> {
                     xrel,
                     yrel,
                     ..
-                } => camera.drag_mouse(
-                    correct_pos((x as _, -y as _), &surface_config, projection_matrix),
-                    correct_rel((xrel as _, -yrel as _), &surface_config, projection_matrix),
-                ),
+                } => {
+                    if three_dimensional {
+                        orbit_camera.drag_mouse(xrel, yrel);
+                    } else {
+                        let config = &renderer.surface_config;
+                        camera.drag_mouse(
+                            correct_pos((x as _, -y as _), config, projection_matrix),
+                            correct_rel((xrel as _, -yrel as _), config, projection_matrix),
+                        );
+                    }
+                }
                 Event::MouseButtonUp { which: 0, .. } => {
                     camera.let_go_of_mouse();
+                    orbit_camera.let_go_of_mouse();
                 }
                 Event::MouseWheel {
                     mut y, direction, ..
                 } => {
                     y *= direction.to_ll() as i32 * 2 - 1;
-                    camera.scroll(y as f32 / 20.0);
+                    if three_dimensional {
+                        orbit_camera.scroll(y as f32 / 20.0);
+                    } else {
+                        camera.scroll(y as f32 / 20.0);
+                    }
                 }
                 _ => {}
             }
         }
 
+        let (width, height) = (
+            renderer.surface_config.width,
+            renderer.surface_config.height,
+        );
         let update_time: std::time::Duration = std::time::Duration::new(0, 16666667);
+        // don't try to catch up on more than a few updates, or one slow frame makes the next
+        // one even slower
+        accumulated_time = accumulated_time.min(update_time * 4);
+        let pixel_size = if three_dimensional {
+            orbit_camera.pixel_size(height)
+        } else {
+            camera.pixel_size(width)
+        } as f64;
+        let display_radii = |particles: &particle::ParticleStore<V>| {
+            particles
+                .iter()
+                .map(|p| species_table.display_radius(p, display_scale, pixel_size))
+                .collect::<Vec<_>>()
+        };
+        if toggle_gpu {
+            gpu_simulation = match gpu_simulation.take() {
+                Some(gpu) => {
+                    gpu.download(&renderer.device, &renderer.queue, &mut simulation.particles);
+                    None
+                }
+                None => {
+                    trails = particle_trail::TrailManager::new();
+                    bond_flashes.clear();
+                    Some(compute::GpuSimulation::new(
+                        &renderer.device,
+                        &renderer.queue,
+                        &simulation.particles,
+                        &display_radii(&simulation.particles),
+                        simulation.force_field.periodic_box.as_ref(),
+                        &potential::Coulomb::default(),
+                        &potential::LennardJones::default(),
+                    ))
+                }
+            };
+        }
+        let mut substeps = 0;
+        if let Some(gpu) = &gpu_simulation {
+            // leaving no trails, bond events or wall pressure, which the GPU doesn't keep
+            while accumulated_time > update_time {
+                gpu.step(&renderer.device, &renderer.queue, update_time.as_secs_f64());
+                substeps += 1;
+                accumulated_time -= update_time;
+            }
+        }
         while accumulated_time > update_time {
             //yay, the update loop
-            particle::Particle::update(&mut particles, update_time.as_secs_f64());
-            trails.update(update_time, &particles);
-            accumulated_time -= update_time
+            substeps += simulation.step(update_time.as_secs_f64()).substeps;
+            trails.update(
+                update_time,
+                &simulation.particles,
+                &display_radii(&simulation.particles),
+            );
+            for flash in &mut bond_flashes {
+                flash.1 -= update_time.as_secs_f64();
+            }
+            bond_flashes.retain(|flash| flash.1 > 0.0);
+            bond_flashes.extend(
+                simulation
+                    .take_bond_events()
+                    .into_iter()
+                    .map(|event| (event, BOND_FLASH_TIME)),
+            );
+            accumulated_time -= update_time;
+            pressure_time += update_time.as_secs_f64();
+        }
+        if pressure_time >= 1.0 && !simulation.walls.is_empty() {
+            let inside = simulation
+                .walls
+                .iter()
+                .map(|w| w.start().to_vec())
+                .sum::<cgmath::Vector2<f64>>()
+                / simulation.walls.len() as f64;
+            wall_pressure = wall::pressure(
+                &simulation.walls,
+                cgmath::Point2::from_vec(inside),
+                pressure_time,
+            );
+            pressure_time = 0.0;
+            for wall in &mut simulation.walls {
+                wall.reset_momentum_transferred();
+            }
         }
 
-        let particle_raws = particles.iter().map(|p| p.to_raw()).collect::<Vec<_>>();
-        let particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("particle buffer"),
-            contents: bytemuck::cast_slice(&particle_raws[..]),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        let projection_name = match (three_dimensional, orbit_camera.is_perspective()) {
+            (false, _) => "",
+            (true, true) => " in perspective",
+            (true, false) => " in orthographic",
+        };
+        // the simulation on the CPU is left behind while the GPU runs, so there's less to say
+        let title = if gpu_simulation.is_some() {
+            format!(
+                "Atomica - {}{} - coulomb + lennard-jones on the gpu - velocity verlet - {} - {} steps",
+                scene_name,
+                projection_name,
+                species_table.census(&simulation.particles),
+                substeps,
+            )
+        } else {
+            format!(
+                "Atomica - {}{} - {}{} - {} - {}{} - {} substeps - E = {:.4} - T = {:.3}{}{}",
+                scene_name,
+                projection_name,
+                model_name,
+                if simulation.force_field.gravity.is_some() {
+                    " + gravity"
+                } else {
+                    ""
+                },
+                simulation.integrator.name(),
+                species_table.census(&simulation.particles),
+                match &simulation.reactions {
+                    Some(_) => format!(" - {} bonds", simulation.force_field.topology.bonds.len()),
+                    None => String::new(),
+                },
+                substeps,
+                simulation.kinetic_energy() + simulation.potential_energy(),
+                simulation.temperature(),
+                match &simulation.thermostat {
+                    Some(thermostat) =>
+                        format!(" ({} to {:.3})", thermostat.kind.name(), thermostat.target),
+                    None => String::new(),
+                },
+                match (simulation.pressure(), &simulation.barostat) {
+                    (Some(pressure), Some(barostat)) => format!(
+                        " - P = {:.4} ({} to {:.3})",
+                        pressure,
+                        barostat.kind.name(),
+                        barostat.target
+                    ),
+                    (Some(pressure), None) => format!(" - P = {:.4}", pressure),
+                    (None, _) if !simulation.walls.is_empty() =>
+                        format!(" - P = {:.4}", wall_pressure),
+                    (None, _) => String::new(),
+                }
+            )
+        };
+        renderer.window.set_title(&title)?;
+
+        let radii = display_radii(&simulation.particles);
+        // positions, display radii and species, in the columns the shaders read them from
+        let mut ghosts: (Vec<[f32; 3]>, Vec<f32>, Vec<u32>) = (vec![], vec![], vec![]);
+        let mut lines = lines::Lines::<V>::new();
+        if let Some(periodic_box) = &simulation.force_field.periodic_box {
+            // draw the periodic images of particles overlapping an edge on the far side as well
+            for (particle, &radius) in simulation.particles.iter().zip(&radii) {
+                for offset in periodic_box.ghost_offsets(particle.position(), radius) {
+                    ghosts
+                        .0
+                        .push(V::render_position(particle.position() + offset));
+                    ghosts.1.push(radius as f32);
+                    ghosts.2.push(particle.species().index());
+                }
+            }
+            lines.push_box(periodic_box.min, periodic_box.max(), [0.35, 0.35, 0.35]);
+        }
+        // walls stand on the xy plane
+        let lift = |p: cgmath::Point2<f64>| V::Point::from_vec(V::from_xy(p.to_vec()));
+        for wall in &simulation.walls {
+            let color = match wall.kind {
+                wall::WallKind::Reflective => [0.8, 0.8, 0.8],
+                wall::WallKind::Absorbing => [0.5, 0.3, 0.9],
+                wall::WallKind::Thermal { .. } => [1.0, 0.55, 0.1],
+            };
+            lines.push(lift(wall.start()), lift(wall.end()), color);
+        }
+        let bonds = match gpu_simulation {
+            Some(_) => vec![],
+            None => simulation
+                .force_field
+                .topology
+                .bonded_pairs(&simulation.particles),
+        };
+        let positions = simulation.particles.positions();
+        for (i, j) in bonds {
+            // bonds that cross an edge of the box are drawn to the nearest image
+            let start = positions[i];
+            let end = start
+                + boundary::displacement(
+                    simulation.force_field.periodic_box.as_ref(),
+                    positions[j],
+                    start,
+                );
+            lines.push(start, end, [0.6, 0.6, 0.6]);
+        }
+        for (event, remaining) in &bond_flashes {
+            let color = match event.kind {
+                reaction::BondEventKind::Formed => [0.3, 1.0, 0.4],
+                reaction::BondEventKind::Broken => [1.0, 0.3, 0.3],
+            };
+            let size = 0.8 * remaining / BOND_FLASH_TIME;
+            for &(dx, dy) in &[(size, size), (size, -size)] {
+                let arm = V::from_xy(cgmath::Vector2::new(dx, dy));
+                lines.push(event.position - arm, event.position + arm, color);
+            }
+        }
+        let device = &renderer.device;
+        let instance_buffer = |label, contents: &[u8]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        };
+        let radii = radii.iter().map(|&r| r as f32).collect::<Vec<_>>();
+        // positions and species go up straight from the simulation's columns, only the radii
+        // depend on the zoom
+        let particle_buffers = [
+            instance_buffer(
+                "particle positions",
+                bytemuck::cast_slice(simulation.particles.render_positions()),
+            ),
+            instance_buffer("particle radii", bytemuck::cast_slice(&radii)),
+            instance_buffer(
+                "particle species",
+                bytemuck::cast_slice(simulation.particles.species()),
+            ),
+        ];
+        let ghost_buffers = [
+            instance_buffer("ghost positions", bytemuck::cast_slice(&ghosts.0)),
+            instance_buffer("ghost radii", bytemuck::cast_slice(&ghosts.1)),
+            instance_buffer("ghost species", bytemuck::cast_slice(&ghosts.2)),
+        ];
 
-        let trail_buffer = trails.get_buffer(&device);
+        let heatmap = if show_heatmap
+            && !three_dimensional
+            && !simulation.force_field.external_potentials.is_empty()
+        {
+            let (min, max) = camera.visible_region(width, height);
+            let columns = 96;
+            let rows = (columns as f32 * (max.y - min.y) / (max.x - min.x)).ceil() as usize;
+            Some(heatmap::Heatmap::new(
+                &simulation.force_field.external_potentials,
+                min.cast().unwrap(),
+                max.cast().unwrap(),
+                columns,
+                rows.max(1),
+            ))
+        } else {
+            None
+        };
+        let heatmap_buffer = heatmap.as_ref().map(|heatmap| heatmap.get_buffer(device));
+        let trail_buffer = trails.get_buffer(device);
+        let line_buffer = lines.get_buffer(device);
 
+        let (view, projection) = if three_dimensional {
+            (
+                orbit_camera.view_matrix(),
+                opengl_to_wgpu * orbit_camera.projection_matrix(width, height),
+            )
+        } else {
+            (camera.create_matrix(), projection_matrix)
+        };
         let transform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("transform buffer"),
-            contents: bytemuck::cast_slice(&cgmath::conv::array4x4(
-                projection_matrix * camera.create_matrix(),
-            )),
+            contents: bytemuck::cast_slice(&[
+                cgmath::conv::array4x4(view),
+                cgmath::conv::array4x4(projection),
+            ]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let palette_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("palette buffer"),
+            contents: bytemuck::bytes_of(&species_table.palette()),
             usage: wgpu::BufferUsages::UNIFORM,
         });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("transform uniform buffer"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer(
-                    transform_buffer.as_entire_buffer_binding(),
-                ),
-            }],
+            layout: &renderer.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(
+                        transform_buffer.as_entire_buffer_binding(),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer(
+                        palette_buffer.as_entire_buffer_binding(),
+                    ),
+                },
+            ],
         });
 
-        let frame = surface
+        let frame = renderer
+            .surface
             .get_current_texture()
             .context("failed to get next frame from surface")?;
         let output = frame
@@ -415,28 +1258,83 @@ fn main() -> color_eyre::Result<()> {
                         store: true,
                     },
                 }],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &renderer.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
             });
-            rpass.set_pipeline(&trail_pipeline);
             rpass.set_bind_group(0, &bind_group, &[]);
+            if let (Some(heatmap), Some(buffer)) = (&heatmap, &heatmap_buffer) {
+                rpass.set_pipeline(&renderer.heatmap_pipeline);
+                rpass.set_vertex_buffer(0, buffer.slice(..));
+                rpass.draw(0..heatmap.len(), 0..1);
+            }
+            // straight from the GPU's own storage buffer when it's running the simulation, and
+            // otherwise the particles followed by their periodic images
+            let (circle_pipeline, sphere_pipeline, mut instances) = match &gpu_simulation {
+                Some(gpu) => (
+                    &renderer.gpu_circle_pipeline,
+                    &renderer.gpu_sphere_pipeline,
+                    vec![(vec![gpu.particle_buffer()], gpu.len())],
+                ),
+                None => (
+                    &renderer.main_circle_pipeline,
+                    &renderer.sphere_pipeline,
+                    vec![
+                        (
+                            particle_buffers.iter().collect(),
+                            simulation.particles.len() as u32,
+                        ),
+                        (ghost_buffers.iter().collect(), ghosts.0.len() as u32),
+                    ],
+                ),
+            };
+            instances.retain(|(_, count)| *count > 0);
+            // solid spheres go first in 3D, so the depth test can hide whatever is behind them
+            let (circle_vertexes, circle_indexes, circle_index_count) = &renderer.circle;
+            let (square_vertexes, square_indexes, square_index_count) = &renderer.square;
+            if three_dimensional {
+                rpass.set_pipeline(sphere_pipeline);
+                rpass.set_vertex_buffer(0, square_vertexes.slice(..));
+                rpass.set_index_buffer(square_indexes.slice(..), wgpu::IndexFormat::Uint16);
+                for (buffers, count) in &instances {
+                    for (slot, buffer) in (1..).zip(buffers) {
+                        rpass.set_vertex_buffer(slot, buffer.slice(..));
+                    }
+                    rpass.draw_indexed(0..*square_index_count, 0, 0..*count);
+                }
+            }
+            if lines.len() > 0 {
+                rpass.set_pipeline(&renderer.line_pipeline);
+                rpass.set_vertex_buffer(0, line_buffer.slice(..));
+                rpass.draw(0..lines.len(), 0..1);
+            }
+            rpass.set_pipeline(&renderer.trail_pipeline);
             rpass.set_vertex_buffer(0, square_vertexes.slice(..));
             rpass.set_vertex_buffer(1, trail_buffer.slice(..));
             rpass.set_index_buffer(square_indexes.slice(..), wgpu::IndexFormat::Uint16);
-            rpass.draw_indexed(0..square_index_count, 0, 0..trails.len());
-            rpass.set_pipeline(&main_circle_pipeline);
-            rpass.set_bind_group(0, &bind_group, &[]);
-            rpass.set_vertex_buffer(0, circle_vertexes.slice(..));
-            rpass.set_vertex_buffer(1, particle_buffer.slice(..));
-            rpass.set_index_buffer(circle_indexes.slice(..), wgpu::IndexFormat::Uint16);
-            rpass.draw_indexed(0..circle_index_count, 0, 0..particles.len() as u32);
+            rpass.draw_indexed(0..*square_index_count, 0, 0..trails.len());
+            if !three_dimensional {
+                rpass.set_pipeline(circle_pipeline);
+                rpass.set_vertex_buffer(0, circle_vertexes.slice(..));
+                rpass.set_index_buffer(circle_indexes.slice(..), wgpu::IndexFormat::Uint16);
+                for (buffers, count) in &instances {
+                    for (slot, buffer) in (1..).zip(buffers) {
+                        rpass.set_vertex_buffer(slot, buffer.slice(..));
+                    }
+                    rpass.draw_indexed(0..*circle_index_count, 0, 0..*count);
+                }
+            }
         }
-        queue.submit([encoder.finish()]);
+        renderer.queue.submit([encoder.finish()]);
         frame.present();
 
         let new_time = std::time::Instant::now();
         accumulated_time += new_time - last_frame;
         last_frame = new_time;
     }
-    println!("Hello, world!");
-    Ok(())
 }
@@ -1,9 +1,9 @@
 #version 440 core
 
-layout(location = 0) in vec3 charge_color;
+layout(location = 0) in vec3 species_color;
 
 layout(location = 0) out vec4 out_color;
 
 void main() {
-    out_color = vec4(charge_color, 1);
+    out_color = vec4(species_color, 1);
 }
@@ -1,29 +1,26 @@
 #version 440 core
 
 layout(location = 0) in vec2 vert_position;
-layout(location = 1) in vec2 particle_position;
+layout(location = 1) in vec3 particle_position;
 layout(location = 2) in float radius;
-layout(location = 3) in float charge;
+layout(location = 3) in uint species;
 
-layout(location = 0) out vec3 charge_color;
+layout(location = 0) out vec3 species_color;
 
 layout(std140, set = 0, binding = 0) uniform transform {
-    mat4 m;
+    mat4 view;
+    mat4 projection;
 };
 
-vec3 get_charge_color(float charge)
-{
-    if (charge == 0) {
-        return vec3(1.0, 1.0, 1.0);
-    } else if (charge < 0) {
-        return vec3(0.286, 0.322, 1.0);
-    } else {
-        return vec3(0.961, 0.0, 0.302);
-    }
-}
+layout(std140, set = 0, binding = 1) uniform palette {
+    vec4 colors[16];
+};
 
 void main() {
-    gl_Position = m * vec4(vert_position * (radius / 2) + particle_position, 0, 1);
-    charge_color = get_charge_color(charge);
-    charge_color = charge_color * charge_color;
+    // the 2D camera zooms by scaling the view
+    float scale = length(view[0].xyz);
+    vec4 center = view * vec4(particle_position, 1);
+    gl_Position = projection * (center + vec4(vert_position * radius * scale, 0, 0));
+    species_color = colors[species].rgb;
+    species_color = species_color * species_color;
 }
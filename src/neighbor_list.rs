@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use crate::{
+    boundary::{self, PeriodicBox},
+    dimension::Vector,
+};
+
+/// Cell coordinates, with the unused ones left at 0 in 2D.
+type Cell = [i64; 3];
+
+/// Offsets to half of the neighbouring cells, the ones whose last non-zero coordinate is
+/// positive, plus the cell itself, so each pair of cells is visited once.
+fn half_neighborhood(dimension: usize) -> Vec<Cell> {
+    (0..3i64.pow(dimension as u32))
+        .map(|index| {
+            let mut offset = [0; 3];
+            let mut rest = index;
+            for c in offset.iter_mut().take(dimension) {
+                *c = rest % 3 - 1;
+                rest /= 3;
+            }
+            offset
+        })
+        .filter(|offset| {
+            offset
+                .iter()
+                .rev()
+                .find(|&&c| c != 0)
+                .is_none_or(|&c| c > 0)
+        })
+        .collect()
+}
+
+/// Every pair of points closer than `range`, found by binning the points into a uniform grid
+/// of cells at least `range` wide so only neighbouring cells have to be compared. With a
+/// periodic box the grid wraps around and distances use the minimum image.
+pub fn cell_list_pairs<V: Vector>(
+    positions: &[V::Point],
+    range: f64,
+    periodic_box: Option<&PeriodicBox<V>>,
+) -> Vec<(usize, usize)> {
+    let range2 = range * range;
+    let close = |i: usize, j: usize| {
+        boundary::displacement(periodic_box, positions[i], positions[j]).magnitude2() < range2
+    };
+
+    // cells wrap around in a periodic box, which needs at least three of them across for the
+    // neighbouring cells to be distinct
+    let wrap = match periodic_box {
+        Some(periodic_box) => {
+            let mut counts = [1; 3];
+            for (i, count) in counts.iter_mut().enumerate().take(V::DIMENSION) {
+                *count = (periodic_box.size[i] / range).floor() as i64;
+            }
+            if counts[..V::DIMENSION].iter().any(|&count| count < 3) {
+                let mut pairs = vec![];
+                for i in 0..positions.len() {
+                    for j in i + 1..positions.len() {
+                        if close(i, j) {
+                            pairs.push((i, j));
+                        }
+                    }
+                }
+                return pairs;
+            }
+            Some((periodic_box, counts))
+        }
+        None => None,
+    };
+    let cell_of = |p: V::Point| {
+        let mut cell = [0; 3];
+        match wrap {
+            Some((periodic_box, counts)) => {
+                let p = periodic_box.wrap(p) - periodic_box.min;
+                for (i, c) in cell.iter_mut().enumerate().take(V::DIMENSION) {
+                    *c = ((p[i] / periodic_box.size[i] * counts[i] as f64) as i64)
+                        .min(counts[i] - 1);
+                }
+            }
+            None => {
+                for (i, c) in cell.iter_mut().enumerate().take(V::DIMENSION) {
+                    *c = (p[i] / range).floor() as i64;
+                }
+            }
+        }
+        cell
+    };
+    let neighbor_of = |cell: Cell, offset: &Cell| {
+        let mut neighbor = [0; 3];
+        for i in 0..3 {
+            neighbor[i] = match wrap {
+                Some((_, counts)) => (cell[i] + offset[i]).rem_euclid(counts[i]),
+                None => cell[i] + offset[i],
+            };
+        }
+        neighbor
+    };
+
+    let mut cells: HashMap<Cell, Vec<usize>> = HashMap::new();
+    for (i, &p) in positions.iter().enumerate() {
+        cells.entry(cell_of(p)).or_default().push(i);
+    }
+
+    let neighborhood = half_neighborhood(V::DIMENSION);
+    let mut pairs = vec![];
+    for (&cell, members) in &cells {
+        for offset in &neighborhood {
+            let others = match cells.get(&neighbor_of(cell, offset)) {
+                Some(others) => others,
+                None => continue,
+            };
+            for (n, &i) in members.iter().enumerate() {
+                let candidates = if *offset == [0; 3] {
+                    &members[n + 1..]
+                } else {
+                    &others[..]
+                };
+                for &j in candidates {
+                    if close(i, j) {
+                        pairs.push((i.min(j), i.max(j)));
+                    }
+                }
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+/// Verlet neighbour list: every pair within the cutoff plus a skin, which stays valid until
+/// some particle has moved more than half the skin since it was built.
+#[derive(Debug, Clone)]
+pub struct NeighborList<V: Vector = cgmath::Vector2<f64>> {
+    pub skin: f64,
+    cutoff: f64,
+    periodic_box: Option<PeriodicBox<V>>,
+    pairs: Vec<(usize, usize)>,
+    reference_positions: Vec<V::Point>,
+    rebuilds: u64,
+}
+
+impl<V: Vector> NeighborList<V> {
+    pub fn new(skin: f64) -> Self {
+        Self {
+            skin,
+            cutoff: 0.0,
+            periodic_box: None,
+            pairs: vec![],
+            reference_positions: vec![],
+            rebuilds: 0,
+        }
+    }
+
+    pub fn is_valid(
+        &self,
+        positions: &[V::Point],
+        cutoff: f64,
+        periodic_box: Option<&PeriodicBox<V>>,
+    ) -> bool {
+        let limit = self.skin / 2.0;
+        cutoff <= self.cutoff
+            && periodic_box == self.periodic_box.as_ref()
+            && positions.len() == self.reference_positions.len()
+            && positions
+                .iter()
+                .zip(&self.reference_positions)
+                .all(|(&p, &r)| {
+                    boundary::displacement(periodic_box, p, r).magnitude2() <= limit * limit
+                })
+    }
+
+    /// Rebuilds the list if it is no longer valid for particles at `positions`.
+    pub fn update(
+        &mut self,
+        positions: &[V::Point],
+        cutoff: f64,
+        periodic_box: Option<&PeriodicBox<V>>,
+    ) {
+        if self.is_valid(positions, cutoff, periodic_box) {
+            return;
+        }
+        self.reference_positions = positions.to_vec();
+        self.cutoff = cutoff;
+        self.periodic_box = periodic_box.copied();
+        self.pairs = cell_list_pairs(&self.reference_positions, cutoff + self.skin, periodic_box);
+        self.rebuilds += 1;
+    }
+
+    pub fn pairs(&self) -> &[(usize, usize)] {
+        &self.pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    fn grid(spacing: f64) -> Vec<cgmath::Point2<f64>> {
+        (0..400)
+            .map(|i| {
+                let jitter = ((i * 7919) % 13) as f64 * 0.01;
+                cgmath::point2(
+                    (i % 20) as f64 * spacing + jitter,
+                    (i / 20) as f64 * spacing - jitter,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cell_list_finds_every_close_pair() {
+        let positions = grid(0.9);
+        let mut brute_force = vec![];
+        for i in 0..positions.len() {
+            for j in i + 1..positions.len() {
+                if (positions[i] - positions[j]).magnitude() < 2.1 {
+                    brute_force.push((i, j));
+                }
+            }
+        }
+        let pairs = cell_list_pairs::<cgmath::Vector2<f64>>(&positions, 2.1, None);
+        assert_eq!(pairs, brute_force);
+    }
+
+    #[test]
+    fn rebuilds_only_after_moving_half_the_skin() {
+        let mut positions = grid(1.0);
+        let mut list: NeighborList = NeighborList::new(0.4);
+        list.update(&positions, 2.5, None);
+        assert_eq!(list.rebuilds, 1);
+
+        positions[17] += cgmath::vec2(0.15, 0.0);
+        list.update(&positions, 2.5, None);
+        assert_eq!(list.rebuilds, 1);
+
+        positions[17] += cgmath::vec2(0.1, 0.0);
+        list.update(&positions, 2.5, None);
+        assert_eq!(list.rebuilds, 2);
+
+        list.update(&positions, 3.0, None);
+        assert_eq!(list.rebuilds, 3);
+    }
+
+    #[test]
+    fn periodic_cell_list_finds_pairs_across_edges() {
+        let periodic_box = PeriodicBox {
+            min: cgmath::point2(-0.5, -0.5),
+            size: cgmath::vec2(20.0 * 0.9, 20.0 * 0.9),
+        };
+        let positions: Vec<_> = grid(0.9)
+            .into_iter()
+            .map(|p| periodic_box.wrap(p))
+            .collect();
+        let mut brute_force = vec![];
+        for i in 0..positions.len() {
+            for j in i + 1..positions.len() {
+                let d = periodic_box.minimum_image(positions[i] - positions[j]);
+                if d.magnitude() < 2.1 {
+                    brute_force.push((i, j));
+                }
+            }
+        }
+        let pairs = cell_list_pairs(&positions, 2.1, Some(&periodic_box));
+        assert!(pairs.contains(&(0, 19)));
+        assert_eq!(pairs, brute_force);
+    }
+
+    #[test]
+    fn periodic_cell_list_works_in_3d() {
+        let periodic_box = PeriodicBox::centered(cgmath::vec3(8.0, 9.0, 10.0));
+        let positions: Vec<_> = (0..500)
+            .map(|i| {
+                let jitter = ((i * 7919) % 13) as f64 * 0.05;
+                periodic_box.wrap(cgmath::point3(
+                    (i % 8) as f64 + jitter,
+                    (i / 8 % 8) as f64 * 1.1,
+                    (i / 64) as f64 * 1.3 - jitter,
+                ))
+            })
+            .collect();
+        let mut brute_force = vec![];
+        for i in 0..positions.len() {
+            for j in i + 1..positions.len() {
+                if periodic_box
+                    .minimum_image(positions[i] - positions[j])
+                    .magnitude()
+                    < 2.1
+                {
+                    brute_force.push((i, j));
+                }
+            }
+        }
+        assert_eq!(
+            cell_list_pairs(&positions, 2.1, Some(&periodic_box)),
+            brute_force
+        );
+        assert_eq!(half_neighborhood(3).len(), 14);
+    }
+}
@@ -1,73 +1,655 @@
-use cgmath::{num_traits::Pow, MetricSpace, Zero};
+use std::{
+    collections::HashMap,
+    iter::FromIterator,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-pub struct Particle {
-    position: cgmath::Point2<f64>,
-    velocity: cgmath::Vector2<f64>,
+use crate::{
+    dimension::Vector,
+    species::{Species, SpeciesId},
+};
+
+/// Identifies a particle for as long as it exists, unlike its index which shifts when other
+/// particles are removed. Clones share the id of the particle they were cloned from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ParticleId(u64);
+
+impl ParticleId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        ParticleId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Particle<V: Vector = cgmath::Vector2<f64>> {
+    id: ParticleId,
+    species: SpeciesId,
+    position: V::Point,
+    velocity: V,
     mass: f64,
     charge: f64,
+    lennard_jones: LennardJonesParameters,
+    radius: f64,
+    valence: u32,
 }
 
-#[repr(C)]
+/// Per-particle Lennard-Jones parameters, combined pairwise with the Lorentz-Berthelot rules.
 #[derive(Debug, Clone, Copy)]
-pub struct RawParticle {
-    position: [f32; 2],
-    radius: f32,
-    charge: f32,
+pub struct LennardJonesParameters {
+    pub epsilon: f64,
+    pub sigma: f64,
 }
 
-unsafe impl bytemuck::Pod for RawParticle {}
-unsafe impl bytemuck::Zeroable for RawParticle {}
+impl Default for LennardJonesParameters {
+    fn default() -> Self {
+        Self {
+            epsilon: 1.0,
+            sigma: 1.0,
+        }
+    }
+}
 
-fn lennard_jones_force(d: f64) -> f64 {
-    const EPSILON: f64 = 10000.0;
-    const TURBO: f64 = 0.345;
-    -(24.0 * EPSILON / d * d) * ((2.0 * TURBO / d).pow(12.0) - (TURBO / d).pow(6))
+impl LennardJonesParameters {
+    pub fn mix(&self, other: &LennardJonesParameters) -> LennardJonesParameters {
+        LennardJonesParameters {
+            epsilon: (self.epsilon * other.epsilon).sqrt(),
+            sigma: (self.sigma + other.sigma) / 2.0,
+        }
+    }
 }
 
-impl Particle {
-    pub fn new(
-        position: cgmath::Point2<f64>,
-        velocity: cgmath::Vector2<f64>,
-        mass: f64,
-        charge: f64,
-    ) -> Self {
+impl<V: Vector> Particle<V> {
+    /// An inert particle with its own mass and charge, drawn as the stock species for the sign
+    /// of its charge.
+    pub fn new(position: V::Point, velocity: V, mass: f64, charge: f64) -> Self {
         Self {
+            id: ParticleId::next(),
+            species: SpeciesId::by_charge(charge),
             position,
             velocity,
             mass,
             charge,
+            lennard_jones: LennardJonesParameters::default(),
+            radius: LennardJonesParameters::default().sigma / 2.0,
+            valence: 0,
+        }
+    }
+
+    /// Usually called through `SpeciesTable::spawn`.
+    pub fn of_species(id: SpeciesId, species: &Species, position: V::Point, velocity: V) -> Self {
+        Self {
+            species: id,
+            lennard_jones: species.lennard_jones,
+            radius: species.radius,
+            valence: species.valence,
+            ..Self::new(position, velocity, species.mass, species.charge)
+        }
+    }
+
+    pub fn id(&self) -> ParticleId {
+        self.id
+    }
+
+    pub fn species(&self) -> SpeciesId {
+        self.species
+    }
+
+    pub fn position(&self) -> V::Point {
+        self.position
+    }
+
+    pub fn velocity(&self) -> V {
+        self.velocity
+    }
+
+    pub fn mass(&self) -> f64 {
+        self.mass
+    }
+
+    pub fn charge(&self) -> f64 {
+        self.charge
+    }
+
+    pub fn lennard_jones(&self) -> &LennardJonesParameters {
+        &self.lennard_jones
+    }
+
+    /// How many bonds the particle can form by reacting, 0 for inert particles.
+    pub fn valence(&self) -> u32 {
+        self.valence
+    }
+
+    /// Contact radius for hard-sphere collisions, and the size drawn at physical scale.
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn set_position(&mut self, position: V::Point) {
+        self.position = position;
+    }
+
+    pub fn set_velocity(&mut self, velocity: V) {
+        self.velocity = velocity;
+    }
+
+    pub fn drift(&mut self, displacement: V) {
+        self.position += displacement;
+    }
+
+    pub fn kick(&mut self, delta_v: V) {
+        self.velocity += delta_v;
+    }
+}
+
+/// Particles kept column by column rather than one struct after another, so a pass over one
+/// property reads nothing but that property. Particles keep their ids as others come and go,
+/// but removing one moves the last particle into its place.
+#[derive(Debug, Clone)]
+pub struct ParticleStore<V: Vector = cgmath::Vector2<f64>> {
+    ids: Vec<ParticleId>,
+    species: Vec<SpeciesId>,
+    positions: Vec<V::Point>,
+    velocities: Vec<V>,
+    masses: Vec<f64>,
+    charges: Vec<f64>,
+    lennard_jones: Vec<LennardJonesParameters>,
+    radii: Vec<f64>,
+    valences: Vec<u32>,
+    /// `positions` in single precision, kept in step with them for the shaders to read.
+    render_positions: Vec<[f32; 3]>,
+    indexes: HashMap<ParticleId, usize>,
+}
+
+impl<V: Vector> ParticleStore<V> {
+    pub fn new() -> Self {
+        Self {
+            ids: vec![],
+            species: vec![],
+            positions: vec![],
+            velocities: vec![],
+            masses: vec![],
+            charges: vec![],
+            lennard_jones: vec![],
+            radii: vec![],
+            valences: vec![],
+            render_positions: vec![],
+            indexes: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Adds `particle` at the end, replacing whatever was stored under the same id.
+    pub fn push(&mut self, particle: Particle<V>) -> ParticleId {
+        let id = particle.id;
+        self.remove(id);
+        self.indexes.insert(id, self.len());
+        self.ids.push(id);
+        self.species.push(particle.species);
+        self.positions.push(particle.position);
+        self.velocities.push(particle.velocity);
+        self.masses.push(particle.mass);
+        self.charges.push(particle.charge);
+        self.lennard_jones.push(particle.lennard_jones);
+        self.radii.push(particle.radius);
+        self.valences.push(particle.valence);
+        self.render_positions
+            .push(V::render_position(particle.position));
+        id
+    }
+
+    /// Takes the particle out, moving the last one into the gap.
+    pub fn remove(&mut self, id: ParticleId) -> Option<Particle<V>> {
+        let index = self.indexes.remove(&id)?;
+        let particle = self.get(index).to_particle();
+        self.ids.swap_remove(index);
+        self.species.swap_remove(index);
+        self.positions.swap_remove(index);
+        self.velocities.swap_remove(index);
+        self.masses.swap_remove(index);
+        self.charges.swap_remove(index);
+        self.lennard_jones.swap_remove(index);
+        self.radii.swap_remove(index);
+        self.valences.swap_remove(index);
+        self.render_positions.swap_remove(index);
+        if let Some(&moved) = self.ids.get(index) {
+            self.indexes.insert(moved, index);
+        }
+        Some(particle)
+    }
+
+    /// Keeps only the particles `keep` says to, in the order they were in.
+    pub fn retain(&mut self, mut keep: impl FnMut(ParticleRef<V>) -> bool) {
+        let kept: Vec<bool> = self.iter().map(&mut keep).collect();
+        fn compact<T>(column: &mut Vec<T>, kept: &[bool]) {
+            let mut kept = kept.iter();
+            column.retain(|_| *kept.next().unwrap());
+        }
+        compact(&mut self.ids, &kept);
+        compact(&mut self.species, &kept);
+        compact(&mut self.positions, &kept);
+        compact(&mut self.velocities, &kept);
+        compact(&mut self.masses, &kept);
+        compact(&mut self.charges, &kept);
+        compact(&mut self.lennard_jones, &kept);
+        compact(&mut self.radii, &kept);
+        compact(&mut self.valences, &kept);
+        compact(&mut self.render_positions, &kept);
+        self.indexes = self
+            .ids
+            .iter()
+            .enumerate()
+            .map(|(i, &id)| (id, i))
+            .collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.ids.clear();
+        self.species.clear();
+        self.positions.clear();
+        self.velocities.clear();
+        self.masses.clear();
+        self.charges.clear();
+        self.lennard_jones.clear();
+        self.radii.clear();
+        self.valences.clear();
+        self.render_positions.clear();
+        self.indexes.clear();
+    }
+
+    /// Where the particle is stored for now, which changes when others are removed.
+    pub fn index_of(&self, id: ParticleId) -> Option<usize> {
+        self.indexes.get(&id).copied()
+    }
+
+    /// Panics if there is no particle at `index`.
+    pub fn get(&self, index: usize) -> ParticleRef<'_, V> {
+        assert!(index < self.len(), "no particle at {}", index);
+        ParticleRef { store: self, index }
+    }
+
+    /// Panics if there is no particle at `index`.
+    pub fn get_mut(&mut self, index: usize) -> ParticleMut<'_, V> {
+        assert!(index < self.len(), "no particle at {}", index);
+        ParticleMut { store: self, index }
+    }
+
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            store: self,
+            indexes: 0..self.len(),
+        }
+    }
+
+    pub fn ids(&self) -> &[ParticleId] {
+        &self.ids
+    }
+
+    pub fn species(&self) -> &[SpeciesId] {
+        &self.species
+    }
+
+    pub fn positions(&self) -> &[V::Point] {
+        &self.positions
+    }
+
+    pub fn velocities(&self) -> &[V] {
+        &self.velocities
+    }
+
+    pub fn masses(&self) -> &[f64] {
+        &self.masses
+    }
+
+    pub fn charges(&self) -> &[f64] {
+        &self.charges
+    }
+
+    pub fn lennard_jones(&self) -> &[LennardJonesParameters] {
+        &self.lennard_jones
+    }
+
+    pub fn radii(&self) -> &[f64] {
+        &self.radii
+    }
+
+    pub fn valences(&self) -> &[u32] {
+        &self.valences
+    }
+
+    /// Positions as the shaders take them, ready for `bytemuck::cast_slice`. 2D positions sit at
+    /// z = 0.
+    pub fn render_positions(&self) -> &[[f32; 3]] {
+        &self.render_positions
+    }
+
+    /// Velocities have nothing kept in step with them, so they can be changed in place.
+    pub fn velocities_mut(&mut self) -> &mut [V] {
+        &mut self.velocities
+    }
+
+    /// Moves every particle to `position(index, old position)`.
+    pub fn move_all(&mut self, mut position: impl FnMut(usize, V::Point) -> V::Point) {
+        for (i, (point, render_position)) in self
+            .positions
+            .iter_mut()
+            .zip(&mut self.render_positions)
+            .enumerate()
+        {
+            *point = position(i, *point);
+            *render_position = V::render_position(*point);
         }
     }
 
-    pub fn update(particles: &mut Vec<Particle>, dt: f64) {
-        let mut forces = Vec::new();
-        forces.resize(particles.len(), cgmath::vec2(0.0, 0.0));
+    /// Free flight: every particle moves on by its velocity times `dt`.
+    pub fn drift(&mut self, dt: f64) {
+        for ((position, &velocity), render_position) in self
+            .positions
+            .iter_mut()
+            .zip(&self.velocities)
+            .zip(&mut self.render_positions)
+        {
+            *position += velocity * dt;
+            *render_position = V::render_position(*position);
+        }
+    }
 
-        //PUT EKSEMPEL HER
+    /// Speeds every particle up by `accelerations[i] * dt`.
+    pub fn accelerate(&mut self, accelerations: &[V], dt: f64) {
+        for (velocity, &acceleration) in self.velocities.iter_mut().zip(accelerations) {
+            *velocity += acceleration * dt;
+        }
+    }
+}
 
-        //ENDE AF EKSEMPEL
+impl<V: Vector> Default for ParticleStore<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        for (particle, force) in particles.iter_mut().zip(forces.iter()) {
-            particle.velocity += (force / particle.mass) * dt;
-            particle.position += particle.velocity * dt;
+impl<V: Vector> Extend<Particle<V>> for ParticleStore<V> {
+    fn extend<I: IntoIterator<Item = Particle<V>>>(&mut self, particles: I) {
+        for particle in particles {
+            self.push(particle);
         }
     }
+}
+
+impl<V: Vector> FromIterator<Particle<V>> for ParticleStore<V> {
+    fn from_iter<I: IntoIterator<Item = Particle<V>>>(particles: I) -> Self {
+        let mut store = Self::new();
+        store.extend(particles);
+        store
+    }
+}
+
+impl<'a, V: Vector> IntoIterator for &'a ParticleStore<V> {
+    type Item = ParticleRef<'a, V>;
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Iter<'a, V> {
+        self.iter()
+    }
+}
+
+/// Every particle of a `ParticleStore` in turn.
+#[derive(Clone)]
+pub struct Iter<'a, V: Vector> {
+    store: &'a ParticleStore<V>,
+    indexes: std::ops::Range<usize>,
+}
+
+impl<'a, V: Vector> Iterator for Iter<'a, V> {
+    type Item = ParticleRef<'a, V>;
+
+    fn next(&mut self) -> Option<ParticleRef<'a, V>> {
+        let index = self.indexes.next()?;
+        Some(ParticleRef {
+            store: self.store,
+            index,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indexes.size_hint()
+    }
+}
+
+impl<'a, V: Vector> DoubleEndedIterator for Iter<'a, V> {
+    fn next_back(&mut self) -> Option<ParticleRef<'a, V>> {
+        let index = self.indexes.next_back()?;
+        Some(ParticleRef {
+            store: self.store,
+            index,
+        })
+    }
+}
+
+impl<'a, V: Vector> ExactSizeIterator for Iter<'a, V> {}
+
+/// One particle of a `ParticleStore`, read straight out of its columns.
+#[derive(Clone, Copy)]
+pub struct ParticleRef<'a, V: Vector = cgmath::Vector2<f64>> {
+    store: &'a ParticleStore<V>,
+    index: usize,
+}
+
+impl<'a, V: Vector> ParticleRef<'a, V> {
+    /// Where it is in the store for now.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn id(&self) -> ParticleId {
+        self.store.ids[self.index]
+    }
+
+    pub fn species(&self) -> SpeciesId {
+        self.store.species[self.index]
+    }
+
+    pub fn position(&self) -> V::Point {
+        self.store.positions[self.index]
+    }
+
+    pub fn velocity(&self) -> V {
+        self.store.velocities[self.index]
+    }
+
+    pub fn mass(&self) -> f64 {
+        self.store.masses[self.index]
+    }
 
-    pub fn create_trail(&self) -> crate::particle_trail::Trail {
+    pub fn charge(&self) -> f64 {
+        self.store.charges[self.index]
+    }
+
+    pub fn lennard_jones(&self) -> &'a LennardJonesParameters {
+        &self.store.lennard_jones[self.index]
+    }
+
+    /// How many bonds the particle can form by reacting, 0 for inert particles.
+    pub fn valence(&self) -> u32 {
+        self.store.valences[self.index]
+    }
+
+    /// Contact radius for hard-sphere collisions, and the size drawn at physical scale.
+    pub fn radius(&self) -> f64 {
+        self.store.radii[self.index]
+    }
+
+    /// A copy of the particle to keep, with the same id.
+    pub fn to_particle(&self) -> Particle<V> {
+        Particle {
+            id: self.id(),
+            species: self.species(),
+            position: self.position(),
+            velocity: self.velocity(),
+            mass: self.mass(),
+            charge: self.charge(),
+            lennard_jones: *self.lennard_jones(),
+            radius: self.radius(),
+            valence: self.valence(),
+        }
+    }
+
+    pub fn create_trail(&self, display_radius: f64) -> crate::particle_trail::Trail {
         crate::particle_trail::Trail::new(
             std::time::Duration::from_secs(3),
-            self.position,
-            self.mass.sqrt(),
-            self.charge,
+            self.store.render_positions[self.index],
+            display_radius,
+            self.species(),
         )
     }
+}
+
+impl<'a, V: Vector> std::fmt::Debug for ParticleRef<'a, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.to_particle().fmt(f)
+    }
+}
+
+/// One particle of a `ParticleStore`, to move or speed up.
+pub struct ParticleMut<'a, V: Vector = cgmath::Vector2<f64>> {
+    store: &'a mut ParticleStore<V>,
+    index: usize,
+}
+
+impl<'a, V: Vector> ParticleMut<'a, V> {
+    pub fn position(&self) -> V::Point {
+        self.store.positions[self.index]
+    }
+
+    pub fn velocity(&self) -> V {
+        self.store.velocities[self.index]
+    }
+
+    pub fn mass(&self) -> f64 {
+        self.store.masses[self.index]
+    }
 
-    pub fn to_raw(&self) -> RawParticle {
-        let pos = self.position;
-        RawParticle {
-            position: [pos.x as _, pos.y as _],
-            radius: self.mass.sqrt() as _,
-            charge: self.charge as _,
+    pub fn charge(&self) -> f64 {
+        self.store.charges[self.index]
+    }
+
+    pub fn set_position(&mut self, position: V::Point) {
+        self.store.positions[self.index] = position;
+        self.store.render_positions[self.index] = V::render_position(position);
+    }
+
+    pub fn set_velocity(&mut self, velocity: V) {
+        self.store.velocities[self.index] = velocity;
+    }
+
+    pub fn drift(&mut self, displacement: V) {
+        self.set_position(self.position() + displacement);
+    }
+
+    pub fn kick(&mut self, delta_v: V) {
+        self.store.velocities[self.index] += delta_v;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn particles() -> Vec<Particle> {
+        (0..5)
+            .map(|i| {
+                Particle::new(
+                    cgmath::point2(i as f64, -(i as f64)),
+                    cgmath::vec2(0.5, i as f64),
+                    1.0 + i as f64,
+                    if i % 2 == 0 { 1.0 } else { -1.0 },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stores_and_gives_back_every_particle() {
+        let particles = particles();
+        let store: ParticleStore = particles.iter().cloned().collect();
+        assert_eq!(store.len(), particles.len());
+        for (stored, particle) in store.iter().zip(&particles) {
+            assert_eq!(stored.id(), particle.id());
+            assert_eq!(stored.species(), particle.species());
+            assert_eq!(stored.position(), particle.position());
+            assert_eq!(stored.velocity(), particle.velocity());
+            assert_eq!(stored.mass(), particle.mass());
+            assert_eq!(stored.charge(), particle.charge());
+        }
+        assert_eq!(store.render_positions()[3], [3.0, -3.0, 0.0]);
+        let floats: &[f32] = bytemuck::cast_slice(store.render_positions());
+        assert_eq!(floats.len(), 3 * particles.len());
+    }
+
+    #[test]
+    fn ids_survive_removal() {
+        let particles = particles();
+        let mut store: ParticleStore = particles.iter().cloned().collect();
+        let removed = store.remove(particles[1].id()).unwrap();
+        assert_eq!(removed.position(), particles[1].position());
+        assert_eq!(store.remove(particles[1].id()).map(|p| p.id()), None);
+        assert_eq!(store.len(), 4);
+        for particle in particles.iter().filter(|p| p.id() != removed.id()) {
+            let index = store.index_of(particle.id()).unwrap();
+            assert_eq!(store.get(index).position(), particle.position());
+            assert_eq!(
+                store.render_positions()[index],
+                <cgmath::Vector2<f64> as Vector>::render_position(particle.position())
+            );
         }
+        // pushing the same particle again replaces it rather than storing it twice
+        store.push(particles[0].clone());
+        assert_eq!(store.len(), 4);
+    }
+
+    #[test]
+    fn drift_and_accelerate_match_the_particles() {
+        let mut particles = particles();
+        let mut store: ParticleStore = particles.iter().cloned().collect();
+        let accelerations: Vec<_> = (0..particles.len())
+            .map(|i| cgmath::vec2(1.0, -(i as f64)))
+            .collect();
+        store.accelerate(&accelerations, 0.1);
+        store.drift(0.1);
+        for ((particle, a), stored) in particles.iter_mut().zip(&accelerations).zip(&store) {
+            particle.kick(a * 0.1);
+            particle.drift(particle.velocity() * 0.1);
+            assert_eq!(stored.velocity(), particle.velocity());
+            assert_eq!(stored.position(), particle.position());
+        }
+        let index = store.len() - 1;
+        let position = store.positions()[index];
+        assert_eq!(
+            store.render_positions()[index],
+            [position.x as f32, position.y as f32, 0.0]
+        );
+    }
+
+    #[test]
+    fn retain_keeps_the_order_and_the_ids() {
+        let particles = particles();
+        let mut store: ParticleStore = particles.iter().cloned().collect();
+        store.retain(|p| p.charge() > 0.0);
+        let kept: Vec<_> = store.ids().to_vec();
+        assert_eq!(
+            kept,
+            [particles[0].id(), particles[2].id(), particles[4].id()]
+        );
+        assert_eq!(store.index_of(particles[4].id()), Some(2));
+        assert_eq!(store.index_of(particles[1].id()), None);
+        store.get_mut(2).set_position(cgmath::point2(7.0, 8.0));
+        assert_eq!(store.render_positions()[2], [7.0, 8.0, 0.0]);
     }
 }
@@ -1,36 +1,37 @@
 use wgpu::{util::DeviceExt, Device};
 
-use crate::particle::Particle;
+use crate::{dimension::Vector, particle::ParticleStore, species::SpeciesId};
 
 #[derive(Debug, Clone)]
 pub struct Trail {
     time_to_live: std::time::Duration,
-    position: cgmath::Point2<f64>,
+    /// With z left at 0 in 2D.
+    position: [f32; 3],
     radius: f64,
-    charge: f64,
+    species: SpeciesId,
 }
 
 impl Trail {
     pub fn new(
         time_to_live: std::time::Duration,
-        position: cgmath::Point2<f64>,
+        position: [f32; 3],
         radius: f64,
-        charge: f64,
+        species: SpeciesId,
     ) -> Self {
         Self {
             time_to_live,
             position,
             radius,
-            charge,
+            species,
         }
     }
 
     fn to_raw(&self) -> RawTrail {
         RawTrail {
             time_to_live: self.time_to_live.as_secs_f32(),
-            position: [self.position.x as _, self.position.y as _],
+            position: self.position,
             radius: self.radius as _,
-            charge: self.charge as _,
+            species: self.species.index(),
         }
     }
 }
@@ -38,15 +39,16 @@ impl Trail {
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct RawTrail {
-    position: [f32; 2],
+    position: [f32; 3],
     time_to_live: f32,
     radius: f32,
-    charge: f32,
+    species: u32,
 }
 
 unsafe impl bytemuck::Zeroable for RawTrail {}
 unsafe impl bytemuck::Pod for RawTrail {}
 
+#[derive(Default)]
 pub struct TrailManager {
     trails: Vec<Trail>,
 }
@@ -56,13 +58,23 @@ impl TrailManager {
         Self { trails: vec![] }
     }
 
-    pub fn update(&mut self, dt: std::time::Duration, particles: &Vec<Particle>) {
+    /// Leaves a new trail behind every particle, as big as `display_radii` says it is drawn.
+    pub fn update<V: Vector>(
+        &mut self,
+        dt: std::time::Duration,
+        particles: &ParticleStore<V>,
+        display_radii: &[f64],
+    ) {
         for trail in &mut self.trails {
             trail.time_to_live = trail.time_to_live.saturating_sub(dt);
         }
         self.trails.retain(|t| !t.time_to_live.is_zero());
-        self.trails
-            .extend(particles.iter().map(|p| p.create_trail()));
+        self.trails.extend(
+            particles
+                .iter()
+                .zip(display_radii)
+                .map(|(p, &radius)| p.create_trail(radius)),
+        );
     }
 
     pub fn get_buffer(&self, device: &Device) -> wgpu::Buffer {
@@ -77,4 +89,8 @@ impl TrailManager {
     pub fn len(&self) -> u32 {
         self.trails.len() as _
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.trails.is_empty()
+    }
 }
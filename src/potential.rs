@@ -0,0 +1,1211 @@
+use rayon::prelude::*;
+
+use crate::{
+    barnes_hut::Tree,
+    boundary::{self, PeriodicBox},
+    dimension::Vector,
+    ewald::{self, EwaldSplit},
+    external_field::ExternalField,
+    external_potential::ExternalPotential,
+    neighbor_list::NeighborList,
+    particle::{ParticleRef, ParticleStore},
+    topology::Topology,
+};
+
+/// A central force law acting between every pair of particles. `Sync` so the forces can be
+/// summed across threads.
+pub trait PairPotential<V: Vector = cgmath::Vector2<f64>>: Sync {
+    /// Radial force between `a` and `b` at separation `r`, positive when repulsive.
+    fn force(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64;
+
+    fn energy(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64;
+
+    /// Separation beyond which the potential is treated as zero between any two of
+    /// `particles`. Potentials whose cutoff depends on the pair report the largest one.
+    fn cutoff(&self, _particles: &ParticleStore<V>) -> Option<f64> {
+        None
+    }
+
+    /// Adds the force this potential exerts on every particle to `forces`. Long-range
+    /// potentials override this to avoid the direct O(n^2) sum.
+    fn accumulate(&self, context: &ForceContext<V>, forces: &mut [V]) {
+        direct_forces(self, context, forces)
+    }
+
+    fn total_energy(&self, context: &ForceContext<V>) -> f64 {
+        direct_energy(self, context)
+    }
+
+    /// Sum of `r . F` over every pair, the potential's contribution to the pressure.
+    fn virial(&self, context: &ForceContext<V>) -> f64 {
+        direct_virial(self, context)
+    }
+}
+
+/// How a long-range potential sums its contributions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Summation {
+    Direct,
+    /// Tree code for open space, any periodic box is ignored.
+    BarnesHut {
+        theta: f64,
+    },
+    /// Ewald summation over every periodic image, with the reciprocal part summed over wave
+    /// vectors of up to `k_max` periods across the box. Direct without a periodic box, and in
+    /// 3D. The periodic sum runs on one thread.
+    Ewald {
+        split: EwaldSplit,
+        k_max: u32,
+    },
+    /// Particle-particle particle-mesh: the real space part of the Ewald sum, with the
+    /// reciprocal part solved with FFTs on a `mesh` x `mesh` grid. Direct without a periodic box,
+    /// and in 3D. The periodic sum runs on one thread.
+    ParticleMesh {
+        split: EwaldSplit,
+        mesh: usize,
+    },
+}
+
+pub struct ForceContext<'a, V: Vector = cgmath::Vector2<f64>> {
+    pub particles: &'a ParticleStore<V>,
+    /// Candidate pairs from the neighbour list, covering every pair within the largest cutoff
+    /// of any potential.
+    pub neighbors: Option<&'a [(usize, usize)]>,
+    pub periodic_box: Option<&'a PeriodicBox<V>>,
+    /// Whether forces are summed across threads.
+    pub parallel: bool,
+}
+
+impl<'a, V: Vector> ForceContext<'a, V> {
+    /// Displacement from particle `j` to particle `i`.
+    pub fn displacement(&self, i: usize, j: usize) -> V {
+        let positions = self.particles.positions();
+        boundary::displacement(self.periodic_box, positions[i], positions[j])
+    }
+
+    /// Calls `f` for every pair that may be closer than `cutoff`.
+    pub fn for_each_pair(&self, cutoff: Option<f64>, mut f: impl FnMut(usize, usize)) {
+        match (cutoff, self.neighbors) {
+            (Some(_), Some(neighbors)) => neighbors.iter().for_each(|&(i, j)| f(i, j)),
+            _ => {
+                for i in 0..self.particles.len() {
+                    for j in i + 1..self.particles.len() {
+                        f(i, j)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every particle's own list of the pairs `for_each_pair` would visit, or `None` when that
+    /// is every other particle.
+    fn adjacency(&self, cutoff: Option<f64>) -> Option<Adjacency> {
+        match (cutoff, self.neighbors) {
+            (Some(_), Some(neighbors)) => Some(Adjacency::new(self.particles.len(), neighbors)),
+            _ => None,
+        }
+    }
+
+    /// Calls `f` once for every particle with its index and the force on it, from as many
+    /// threads as there are. Particles are handed out whole, so each force is summed by one
+    /// thread in the same order however many there are.
+    pub fn gather(&self, forces: &mut [V], f: impl Fn(usize, &mut V) + Sync + Send) {
+        if self.parallel {
+            forces
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(i, force)| f(i, force));
+        } else {
+            forces
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, force)| f(i, force));
+        }
+    }
+}
+
+/// The neighbours of every particle one after another, `offsets[i]..offsets[i + 1]` of them
+/// belonging to particle `i`.
+struct Adjacency {
+    offsets: Vec<usize>,
+    neighbors: Vec<usize>,
+}
+
+impl Adjacency {
+    fn new(count: usize, pairs: &[(usize, usize)]) -> Self {
+        let mut offsets = vec![0; count + 1];
+        for &(i, j) in pairs {
+            offsets[i + 1] += 1;
+            offsets[j + 1] += 1;
+        }
+        for i in 0..count {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut next = offsets.clone();
+        let mut neighbors = vec![0; 2 * pairs.len()];
+        for &(i, j) in pairs {
+            neighbors[next[i]] = j;
+            next[i] += 1;
+            neighbors[next[j]] = i;
+            next[j] += 1;
+        }
+        Self { offsets, neighbors }
+    }
+
+    fn of(&self, i: usize) -> &[usize] {
+        &self.neighbors[self.offsets[i]..self.offsets[i + 1]]
+    }
+}
+
+pub fn direct_forces<V: Vector, P: PairPotential<V> + ?Sized>(
+    potential: &P,
+    context: &ForceContext<V>,
+    forces: &mut [V],
+) {
+    let cutoff = potential.cutoff(context.particles);
+    let particles = context.particles;
+    let pair_force = |i: usize, j: usize| {
+        let r = context.displacement(i, j);
+        let d = r.magnitude();
+        if d == 0.0 || cutoff.is_some_and(|c| d >= c) {
+            return None;
+        }
+        Some(r * (potential.force(d, particles.get(i), particles.get(j)) / d))
+    };
+    if !context.parallel {
+        context.for_each_pair(cutoff, |i, j| {
+            if let Some(force) = pair_force(i, j) {
+                forces[i] += force;
+                forces[j] -= force;
+            }
+        });
+        return;
+    }
+    // every pair is visited from both ends, twice the work of the serial sum but with no two
+    // threads ever adding to the same force
+    let adjacency = context.adjacency(cutoff);
+    context.gather(forces, |i, force| {
+        let mut total = V::zero();
+        let mut add = |j: usize| {
+            if let Some(f) = pair_force(i, j) {
+                total += f;
+            }
+        };
+        match &adjacency {
+            Some(adjacency) => adjacency.of(i).iter().for_each(|&j| add(j)),
+            None => (0..particles.len()).filter(|&j| j != i).for_each(add),
+        }
+        *force += total;
+    });
+}
+
+pub fn direct_energy<V: Vector, P: PairPotential<V> + ?Sized>(
+    potential: &P,
+    context: &ForceContext<V>,
+) -> f64 {
+    let cutoff = potential.cutoff(context.particles);
+    let particles = context.particles;
+    let mut energy = 0.0;
+    context.for_each_pair(cutoff, |i, j| {
+        let (a, b) = (particles.get(i), particles.get(j));
+        let d = context.displacement(i, j).magnitude();
+        if cutoff.is_none_or(|c| d < c) {
+            energy += potential.energy(d, a, b);
+        }
+    });
+    energy
+}
+
+pub fn direct_virial<V: Vector, P: PairPotential<V> + ?Sized>(
+    potential: &P,
+    context: &ForceContext<V>,
+) -> f64 {
+    let cutoff = potential.cutoff(context.particles);
+    let particles = context.particles;
+    let mut virial = 0.0;
+    context.for_each_pair(cutoff, |i, j| {
+        let d = context.displacement(i, j).magnitude();
+        if d > 0.0 && cutoff.is_none_or(|c| d < c) {
+            virial += potential.force(d, particles.get(i), particles.get(j)) * d;
+        }
+    });
+    virial
+}
+
+/// The set of potentials acting on a simulation, along with the neighbour list used to speed
+/// up the ones with a cutoff.
+pub struct ForceField<V: Vector = cgmath::Vector2<f64>> {
+    pub potentials: Vec<Box<dyn PairPotential<V>>>,
+    /// Bonds and angles, which act on top of the pair potentials: bonded particles still feel
+    /// each other through those too.
+    pub topology: Topology,
+    /// Only the electric part is included in `forces`, the integrators deal with the magnetic
+    /// part since it depends on the velocities.
+    pub external_field: ExternalField<V>,
+    /// Landscapes every particle sits in. Their forces come from outside the system, so they
+    /// are left out of the virial and the pressure.
+    pub external_potentials: Vec<ExternalPotential<V>>,
+    /// Kept apart from `potentials` so it can be switched on and off alongside any of them.
+    pub gravity: Option<Gravity>,
+    /// Set to `None` to always check every pair.
+    pub neighbor_list: Option<NeighborList<V>>,
+    pub periodic_box: Option<PeriodicBox<V>>,
+    /// Sums the pair forces on every thread rayon has. The result is bit for bit the same
+    /// whatever the thread count, but differs in the last bits from the serial sum, which adds
+    /// the pairs in another order. Ewald and particle-mesh sums stay serial. Off by default.
+    pub parallel: bool,
+}
+
+impl<V: Vector> ForceField<V> {
+    pub fn new(potentials: Vec<Box<dyn PairPotential<V>>>) -> Self {
+        Self {
+            potentials,
+            topology: Topology::default(),
+            external_field: ExternalField::default(),
+            external_potentials: vec![],
+            gravity: None,
+            neighbor_list: Some(NeighborList::new(0.5)),
+            periodic_box: None,
+            parallel: false,
+        }
+    }
+
+    /// The largest cutoff of the potentials that have one, `None` if none do.
+    fn cutoff(&self, particles: &ParticleStore<V>) -> Option<f64> {
+        self.potentials
+            .iter()
+            .filter_map(|p| p.cutoff(particles))
+            .fold(None, |max: Option<f64>, c| {
+                Some(max.map_or(c, |m| m.max(c)))
+            })
+    }
+
+    /// Sums the force every potential exerts on every particle.
+    pub fn forces(&mut self, particles: &ParticleStore<V>) -> Vec<V> {
+        let cutoff = self.cutoff(particles);
+        let neighbors = match (&mut self.neighbor_list, cutoff) {
+            (Some(list), Some(cutoff)) => {
+                list.update(particles.positions(), cutoff, self.periodic_box.as_ref());
+                Some(list.pairs())
+            }
+            _ => None,
+        };
+        let context = ForceContext {
+            particles,
+            neighbors,
+            periodic_box: self.periodic_box.as_ref(),
+            parallel: self.parallel,
+        };
+        let mut forces = vec![V::zero(); particles.len()];
+        for potential in &self.potentials {
+            potential.accumulate(&context, &mut forces);
+        }
+        if let Some(gravity) = &self.gravity {
+            gravity.accumulate(&context, &mut forces);
+        }
+        self.topology
+            .accumulate(particles, self.periodic_box.as_ref(), &mut forces);
+        if !self.external_field.is_zero() {
+            for (force, particle) in forces.iter_mut().zip(particles) {
+                *force += self.external_field.electric_force(particle);
+            }
+        }
+        for potential in &self.external_potentials {
+            for (force, particle) in forces.iter_mut().zip(particles) {
+                *force += potential.force(particle);
+            }
+        }
+        forces
+    }
+
+    pub fn potential_energy(&self, particles: &ParticleStore<V>) -> f64 {
+        let context = self.context(particles);
+        self.potentials
+            .iter()
+            .map(|p| p.total_energy(&context))
+            .sum::<f64>()
+            + self.gravity.map_or(0.0, |g| g.total_energy(&context))
+            + self.topology.energy(particles, self.periodic_box.as_ref())
+            + particles
+                .iter()
+                .map(|p| self.external_field.energy(p))
+                .sum::<f64>()
+            + self
+                .external_potentials
+                .iter()
+                .flat_map(|potential| particles.iter().map(move |p| potential.energy(p)))
+                .sum::<f64>()
+    }
+
+    pub fn virial(&self, particles: &ParticleStore<V>) -> f64 {
+        let context = self.context(particles);
+        self.potentials
+            .iter()
+            .map(|p| p.virial(&context))
+            .sum::<f64>()
+            + self.gravity.map_or(0.0, |g| g.virial(&context))
+            + self.topology.virial(particles, self.periodic_box.as_ref())
+    }
+
+    /// Context for finding the pairs closer than `range`, which only shares the neighbour list
+    /// while it is valid and covers that range.
+    pub fn context_within<'a>(
+        &'a self,
+        particles: &'a ParticleStore<V>,
+        range: f64,
+    ) -> ForceContext<'a, V> {
+        let mut context = self.context(particles);
+        if self.cutoff(particles).is_none_or(|cutoff| cutoff < range) {
+            context.neighbors = None;
+        }
+        context
+    }
+
+    /// Context for evaluating energies without updating the neighbour list, which is only
+    /// used while it is still valid.
+    fn context<'a>(&'a self, particles: &'a ParticleStore<V>) -> ForceContext<'a, V> {
+        let neighbors = match (&self.neighbor_list, self.cutoff(particles)) {
+            (Some(list), Some(cutoff))
+                if list.is_valid(particles.positions(), cutoff, self.periodic_box.as_ref()) =>
+            {
+                Some(list.pairs())
+            }
+            _ => None,
+        };
+        ForceContext {
+            particles,
+            neighbors,
+            periodic_box: self.periodic_box.as_ref(),
+            parallel: self.parallel,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Coulomb {
+    pub k: f64,
+    /// Plummer-style softening length, keeps the force finite when two charges overlap.
+    pub softening: f64,
+    pub summation: Summation,
+}
+
+impl Default for Coulomb {
+    fn default() -> Self {
+        Self {
+            k: 1.0,
+            softening: 0.05,
+            summation: Summation::Direct,
+        }
+    }
+}
+
+/// A Barnes-Hut tree over every particle, each as strong a source as `strengths` says.
+fn tree<V: Vector>(particles: &ParticleStore<V>, theta: f64, strengths: &[f64]) -> Tree<V> {
+    Tree::new(
+        particles
+            .positions()
+            .iter()
+            .copied()
+            .zip(strengths.iter().copied())
+            .collect(),
+        theta,
+    )
+}
+
+impl Coulomb {
+    /// Energy of the periodic summations, adding their forces to `forces`. `None` if the
+    /// summation isn't periodic, there is no box, or it isn't 2D.
+    fn periodic_sum<V: Vector>(&self, context: &ForceContext<V>, forces: &mut [V]) -> Option<f64> {
+        let (particles, periodic_box) = V::planar(context.particles, context.periodic_box?)?;
+        let mut unit = vec![cgmath::vec2(0.0, 0.0); particles.len()];
+        let (split, reciprocal) = match self.summation {
+            Summation::Ewald { split, k_max } => (
+                split,
+                ewald::reciprocal_sum(particles, periodic_box, split.alpha, k_max, &mut unit),
+            ),
+            Summation::ParticleMesh { split, mesh } => (
+                split,
+                ewald::reciprocal_mesh(particles, periodic_box, split.alpha, mesh, &mut unit),
+            ),
+            Summation::Direct | Summation::BarnesHut { .. } => return None,
+        };
+        let energy = reciprocal
+            + ewald::real_space(particles, periodic_box, split, self.softening, &mut unit)
+            + ewald::self_energy(particles, periodic_box, split.alpha);
+        for (force, unit) in forces.iter_mut().zip(unit) {
+            *force += V::from_xy(unit * self.k);
+        }
+        Some(self.k * energy)
+    }
+}
+
+impl<V: Vector> PairPotential<V> for Coulomb {
+    fn force(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        let d2 = r * r + self.softening * self.softening;
+        self.k * a.charge() * b.charge() * r / (d2 * d2.sqrt())
+    }
+
+    fn energy(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        self.k * a.charge() * b.charge() / (r * r + self.softening * self.softening).sqrt()
+    }
+
+    fn accumulate(&self, context: &ForceContext<V>, forces: &mut [V]) {
+        let particles = context.particles;
+        if self.periodic_sum(context, forces).is_some() {
+            return;
+        }
+        match self.summation {
+            Summation::Direct | Summation::Ewald { .. } | Summation::ParticleMesh { .. } => {
+                direct_forces(self, context, forces)
+            }
+            Summation::BarnesHut { theta } => {
+                let tree = tree(particles, theta, particles.charges());
+                context.gather(forces, |i, force| {
+                    let particle = particles.get(i);
+                    let field = tree.field(particle.position(), self.softening, Some(i));
+                    *force += field * (self.k * particle.charge());
+                });
+            }
+        }
+    }
+
+    fn total_energy(&self, context: &ForceContext<V>) -> f64 {
+        let particles = context.particles;
+        let mut forces = vec![V::zero(); particles.len()];
+        if let Some(energy) = self.periodic_sum(context, &mut forces) {
+            return energy;
+        }
+        match self.summation {
+            Summation::Direct | Summation::Ewald { .. } | Summation::ParticleMesh { .. } => {
+                direct_energy(self, context)
+            }
+            Summation::BarnesHut { theta } => {
+                let tree = tree(particles, theta, particles.charges());
+                let sum: f64 = particles
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| {
+                        p.charge() * tree.potential(p.position(), self.softening, Some(i))
+                    })
+                    .sum();
+                0.5 * self.k * sum
+            }
+        }
+    }
+
+    fn virial(&self, context: &ForceContext<V>) -> f64 {
+        match self.summation {
+            Summation::Direct => direct_virial(self, context),
+            // 1/r is homogeneous of degree -1, so apart from the softening the virial is just
+            // the energy, however it was summed
+            Summation::BarnesHut { .. }
+            | Summation::Ewald { .. }
+            | Summation::ParticleMesh { .. } => self.total_energy(context),
+        }
+    }
+}
+
+/// Newtonian attraction between masses, `-g * m1 * m2 / sqrt(r^2 + softening^2)`, whatever
+/// their charges. Only `Summation::BarnesHut` speeds it up: the periodic summations need the
+/// sources to cancel out, which masses never do, so they fall back to summing directly.
+#[derive(Debug, Clone, Copy)]
+pub struct Gravity {
+    pub g: f64,
+    /// Plummer softening length, which keeps close encounters from flinging bodies apart.
+    pub softening: f64,
+    pub summation: Summation,
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Self {
+            g: 1.0,
+            softening: 0.2,
+            summation: Summation::Direct,
+        }
+    }
+}
+
+impl<V: Vector> PairPotential<V> for Gravity {
+    fn force(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        let d2 = r * r + self.softening * self.softening;
+        -self.g * a.mass() * b.mass() * r / (d2 * d2.sqrt())
+    }
+
+    fn energy(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        -self.g * a.mass() * b.mass() / (r * r + self.softening * self.softening).sqrt()
+    }
+
+    fn accumulate(&self, context: &ForceContext<V>, forces: &mut [V]) {
+        let particles = context.particles;
+        match self.summation {
+            Summation::BarnesHut { theta } => {
+                let tree = tree(particles, theta, particles.masses());
+                context.gather(forces, |i, force| {
+                    let particle = particles.get(i);
+                    let field = tree.field(particle.position(), self.softening, Some(i));
+                    *force -= field * (self.g * particle.mass());
+                });
+            }
+            Summation::Direct | Summation::Ewald { .. } | Summation::ParticleMesh { .. } => {
+                direct_forces(self, context, forces)
+            }
+        }
+    }
+
+    fn total_energy(&self, context: &ForceContext<V>) -> f64 {
+        let particles = context.particles;
+        match self.summation {
+            Summation::BarnesHut { theta } => {
+                let tree = tree(particles, theta, particles.masses());
+                let sum: f64 = particles
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| p.mass() * tree.potential(p.position(), self.softening, Some(i)))
+                    .sum();
+                -0.5 * self.g * sum
+            }
+            Summation::Direct | Summation::Ewald { .. } | Summation::ParticleMesh { .. } => {
+                direct_energy(self, context)
+            }
+        }
+    }
+
+    fn virial(&self, context: &ForceContext<V>) -> f64 {
+        match self.summation {
+            // homogeneous of degree -1 like Coulomb, so the energy again
+            Summation::BarnesHut { .. } => self.total_energy(context),
+            Summation::Direct | Summation::Ewald { .. } | Summation::ParticleMesh { .. } => {
+                direct_virial(self, context)
+            }
+        }
+    }
+}
+
+/// Magnitude of the Lennard-Jones force at distance `d`, positive when repulsive.
+pub fn lennard_jones_force(d: f64, epsilon: f64, sigma: f64) -> f64 {
+    let s6 = (sigma / d).powi(6);
+    24.0 * epsilon / d * (2.0 * s6 * s6 - s6)
+}
+
+pub fn lennard_jones_energy(d: f64, epsilon: f64, sigma: f64) -> f64 {
+    let s6 = (sigma / d).powi(6);
+    4.0 * epsilon * (s6 * s6 - s6)
+}
+
+/// Lennard-Jones 12-6 potential using each particle's own epsilon and sigma. The energy is
+/// shifted to be zero at the cutoff so it stays conserved when pairs cross it.
+#[derive(Debug, Clone, Copy)]
+pub struct LennardJones {
+    /// In units of the pair's mixed sigma.
+    pub cutoff: f64,
+}
+
+impl Default for LennardJones {
+    fn default() -> Self {
+        Self { cutoff: 2.5 }
+    }
+}
+
+/// The largest sigma of any pair, which is the largest any particle has since mixing averages
+/// them.
+fn max_sigma<V: Vector>(particles: &ParticleStore<V>) -> f64 {
+    particles
+        .lennard_jones()
+        .iter()
+        .map(|p| p.sigma)
+        .fold(0.0, f64::max)
+}
+
+impl<V: Vector> PairPotential<V> for LennardJones {
+    fn force(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        let params = a.lennard_jones().mix(b.lennard_jones());
+        if r >= self.cutoff * params.sigma {
+            return 0.0;
+        }
+        lennard_jones_force(r, params.epsilon, params.sigma)
+    }
+
+    fn energy(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        let params = a.lennard_jones().mix(b.lennard_jones());
+        let cutoff = self.cutoff * params.sigma;
+        if r >= cutoff {
+            return 0.0;
+        }
+        lennard_jones_energy(r, params.epsilon, params.sigma)
+            - lennard_jones_energy(cutoff, params.epsilon, params.sigma)
+    }
+
+    fn cutoff(&self, particles: &ParticleStore<V>) -> Option<f64> {
+        Some(self.cutoff * max_sigma(particles))
+    }
+}
+
+/// `depth * (1 - exp(-width * (r - equilibrium)))^2 - depth`
+#[derive(Debug, Clone, Copy)]
+pub struct Morse {
+    pub depth: f64,
+    pub width: f64,
+    pub equilibrium: f64,
+    pub cutoff: Option<f64>,
+}
+
+impl<V: Vector> PairPotential<V> for Morse {
+    fn force(&self, r: f64, _: ParticleRef<V>, _: ParticleRef<V>) -> f64 {
+        let e = (-self.width * (r - self.equilibrium)).exp();
+        -2.0 * self.depth * self.width * e * (1.0 - e)
+    }
+
+    fn energy(&self, r: f64, _: ParticleRef<V>, _: ParticleRef<V>) -> f64 {
+        let e = (-self.width * (r - self.equilibrium)).exp();
+        self.depth * (1.0 - e) * (1.0 - e) - self.depth
+    }
+
+    fn cutoff(&self, _: &ParticleStore<V>) -> Option<f64> {
+        self.cutoff
+    }
+}
+
+/// Debye-screened Coulomb interaction, `k * q1 * q2 * exp(-r / screening_length) / r`.
+#[derive(Debug, Clone, Copy)]
+pub struct Yukawa {
+    pub k: f64,
+    pub screening_length: f64,
+    pub cutoff: Option<f64>,
+}
+
+impl<V: Vector> PairPotential<V> for Yukawa {
+    fn force(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        let screening = (-r / self.screening_length).exp();
+        self.k
+            * a.charge()
+            * b.charge()
+            * screening
+            * (1.0 / (r * r) + 1.0 / (self.screening_length * r))
+    }
+
+    fn energy(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        self.k * a.charge() * b.charge() * (-r / self.screening_length).exp() / r
+    }
+
+    fn cutoff(&self, _: &ParticleStore<V>) -> Option<f64> {
+        self.cutoff
+    }
+}
+
+/// `stiffness / 2 * (r - rest_length)^2` between every pair within the cutoff.
+#[derive(Debug, Clone, Copy)]
+pub struct HarmonicSpring {
+    pub stiffness: f64,
+    pub rest_length: f64,
+    pub cutoff: Option<f64>,
+}
+
+impl<V: Vector> PairPotential<V> for HarmonicSpring {
+    fn force(&self, r: f64, _: ParticleRef<V>, _: ParticleRef<V>) -> f64 {
+        -self.stiffness * (r - self.rest_length)
+    }
+
+    fn energy(&self, r: f64, _: ParticleRef<V>, _: ParticleRef<V>) -> f64 {
+        0.5 * self.stiffness * (r - self.rest_length).powi(2)
+    }
+
+    fn cutoff(&self, _: &ParticleStore<V>) -> Option<f64> {
+        self.cutoff
+    }
+}
+
+/// Purely repulsive `epsilon * (sigma / r)^exponent`, shifted to zero at the cutoff, with
+/// epsilon and sigma mixed from the particles' Lennard-Jones parameters.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftSphere {
+    pub exponent: i32,
+    /// In units of the pair's mixed sigma.
+    pub cutoff: f64,
+}
+
+impl Default for SoftSphere {
+    fn default() -> Self {
+        Self {
+            exponent: 12,
+            cutoff: 2.5,
+        }
+    }
+}
+
+impl<V: Vector> PairPotential<V> for SoftSphere {
+    fn force(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        let params = a.lennard_jones().mix(b.lennard_jones());
+        if r >= self.cutoff * params.sigma {
+            return 0.0;
+        }
+        self.exponent as f64 * params.epsilon * (params.sigma / r).powi(self.exponent) / r
+    }
+
+    fn energy(&self, r: f64, a: ParticleRef<V>, b: ParticleRef<V>) -> f64 {
+        let params = a.lennard_jones().mix(b.lennard_jones());
+        if r >= self.cutoff * params.sigma {
+            return 0.0;
+        }
+        params.epsilon * ((params.sigma / r).powi(self.exponent) - self.cutoff.powi(-self.exponent))
+    }
+
+    fn cutoff(&self, particles: &ParticleStore<V>) -> Option<f64> {
+        Some(self.cutoff * max_sigma(particles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use cgmath::InnerSpace;
+
+    fn pair(distance: f64, q1: f64, q2: f64) -> ParticleStore {
+        vec![
+            Particle::new(cgmath::point2(0.0, 0.0), cgmath::vec2(0.0, 0.0), 1.0, q1),
+            Particle::new(
+                cgmath::point2(distance, 0.0),
+                cgmath::vec2(0.0, 0.0),
+                2.0,
+                q2,
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn all_potentials() -> Vec<Box<dyn PairPotential>> {
+        vec![
+            Box::new(Coulomb::default()),
+            Box::new(LennardJones::default()),
+            Box::new(Morse {
+                depth: 2.0,
+                width: 1.5,
+                equilibrium: 1.2,
+                cutoff: None,
+            }),
+            Box::new(Yukawa {
+                k: 1.0,
+                screening_length: 0.7,
+                cutoff: None,
+            }),
+            Box::new(HarmonicSpring {
+                stiffness: 3.0,
+                rest_length: 1.1,
+                cutoff: None,
+            }),
+            Box::new(SoftSphere::default()),
+            Box::new(Gravity::default()),
+        ]
+    }
+
+    #[test]
+    fn force_is_negative_energy_gradient() {
+        let particles = pair(1.0, 1.0, -2.0);
+        let (a, b) = (particles.get(0), particles.get(1));
+        let h = 1e-6;
+        for potential in all_potentials() {
+            for &r in &[0.9, 1.1, 1.3, 2.0] {
+                let numeric =
+                    -(potential.energy(r + h, a, b) - potential.energy(r - h, a, b)) / (2.0 * h);
+                let analytic = potential.force(r, a, b);
+                assert!(
+                    (numeric - analytic).abs() < 1e-5 * analytic.abs().max(1.0),
+                    "r = {}: {} vs {}",
+                    r,
+                    numeric,
+                    analytic
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn forces_obey_newtons_third_law() {
+        let mut particles = pair(1.5, 1.0, -2.0);
+        particles.push(Particle::new(
+            cgmath::point2(0.7, -1.1),
+            cgmath::vec2(0.0, 0.0),
+            1.0,
+            1.0,
+        ));
+        let forces = ForceField::new(all_potentials()).forces(&particles);
+        let total: cgmath::Vector2<f64> = forces.iter().sum();
+        assert!(total.magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn coulomb_falls_off_with_inverse_square() {
+        let coulomb = Coulomb {
+            softening: 0.0,
+            ..Coulomb::default()
+        };
+        let particles = pair(1.0, 1.0, 1.0);
+        let (a, b) = (particles.get(0), particles.get(1));
+        assert!((coulomb.force(1.0, a, b) / coulomb.force(2.0, a, b) - 4.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn coulomb_sign() {
+        let mut coulomb = ForceField::new(vec![Box::new(Coulomb::default())]);
+        // particle 0 sits left of particle 1, so repulsion pushes it towards -x
+        assert!(coulomb.forces(&pair(1.0, 1.0, 1.0))[0].x < 0.0);
+        assert!(coulomb.forces(&pair(1.0, 1.0, -1.0))[0].x > 0.0);
+    }
+
+    #[test]
+    fn lennard_jones_shape() {
+        let minimum = 2f64.powf(1.0 / 6.0);
+        assert!(lennard_jones_force(minimum, 1.0, 1.0).abs() < 1e-12);
+        assert!(lennard_jones_force(0.9, 1.0, 1.0) > 0.0);
+        assert!(lennard_jones_force(1.5, 1.0, 1.0) < 0.0);
+        // 24 * epsilon / sigma at d = sigma
+        assert!((lennard_jones_force(2.0, 3.0, 2.0) - 36.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn lennard_jones_respects_cutoff() {
+        let mut lennard_jones = ForceField::new(vec![Box::new(LennardJones::default())]);
+        assert!(lennard_jones.forces(&pair(2.4, 0.0, 0.0))[0].magnitude() > 0.0);
+        assert_eq!(
+            lennard_jones.forces(&pair(2.6, 0.0, 0.0))[0].magnitude(),
+            0.0
+        );
+
+        // in units of the mixed sigma, so wider particles stay in range for longer
+        let species = crate::species::Species::new("wide", 1.0, 0.0, [1.0, 1.0, 1.0])
+            .with_lennard_jones(crate::particle::LennardJonesParameters {
+                epsilon: 1.0,
+                sigma: 2.0,
+            });
+        let wide = |x: f64| {
+            Particle::of_species(
+                crate::species::SpeciesId::NEUTRAL,
+                &species,
+                cgmath::point2(x, 0.0),
+                cgmath::vec2(0.0, 0.0),
+            )
+        };
+        let particles: ParticleStore = vec![wide(0.0), wide(4.9)].into_iter().collect();
+        assert!(lennard_jones.forces(&particles)[0].magnitude() > 0.0);
+        assert!(lennard_jones.potential_energy(&particles) < 0.0);
+        let particles: ParticleStore = vec![wide(0.0), wide(5.1)].into_iter().collect();
+        assert_eq!(lennard_jones.forces(&particles)[0].magnitude(), 0.0);
+        assert_eq!(lennard_jones.potential_energy(&particles), 0.0);
+    }
+
+    #[test]
+    fn barnes_hut_coulomb_matches_direct() {
+        let particles: ParticleStore = (0..200)
+            .map(|i| {
+                let angle = i as f64 * 2.4;
+                let radius = (i as f64).sqrt();
+                Particle::new(
+                    cgmath::point2(radius * angle.cos(), radius * angle.sin()),
+                    cgmath::vec2(0.0, 0.0),
+                    1.0,
+                    if i % 3 == 0 { -2.0 } else { 1.0 },
+                )
+            })
+            .collect();
+        let mut direct = ForceField::new(vec![Box::new(Coulomb::default())]);
+        let mut tree = ForceField::new(vec![Box::new(Coulomb {
+            summation: Summation::BarnesHut { theta: 0.4 },
+            ..Coulomb::default()
+        })]);
+        let exact = direct.forces(&particles);
+        let approximate = tree.forces(&particles);
+        let error: f64 = exact
+            .iter()
+            .zip(&approximate)
+            .map(|(a, b)| (a - b).magnitude2())
+            .sum();
+        let total: f64 = exact.iter().map(|f| f.magnitude2()).sum();
+        assert!((error / total).sqrt() < 1e-2);
+        let exact_energy = direct.potential_energy(&particles);
+        let approximate_energy = tree.potential_energy(&particles);
+        assert!((exact_energy - approximate_energy).abs() < 1e-2 * exact_energy.abs());
+    }
+
+    #[test]
+    fn gravity_attracts_whatever_the_charges() {
+        let mut gravity = ForceField::new(vec![]);
+        gravity.gravity = Some(Gravity::default());
+        for &(q1, q2) in &[(0.0, 0.0), (1.0, 1.0), (1.0, -1.0)] {
+            let forces = gravity.forces(&pair(3.0, q1, q2));
+            assert!(forces[0].x > 0.0);
+            assert_eq!(forces[0], -forces[1]);
+        }
+    }
+
+    #[test]
+    fn barnes_hut_gravity_matches_direct() {
+        let particles: ParticleStore = (0..200)
+            .map(|i| {
+                let angle = i as f64 * 2.4;
+                let radius = (i as f64).sqrt();
+                Particle::new(
+                    cgmath::point2(radius * angle.cos(), radius * angle.sin()),
+                    cgmath::vec2(0.0, 0.0),
+                    1.0 + (i % 5) as f64,
+                    0.0,
+                )
+            })
+            .collect();
+        let with_gravity = |summation| ForceField {
+            gravity: Some(Gravity {
+                summation,
+                ..Gravity::default()
+            }),
+            ..ForceField::new(vec![])
+        };
+        let mut direct = with_gravity(Summation::Direct);
+        let mut tree = with_gravity(Summation::BarnesHut { theta: 0.4 });
+        let exact = direct.forces(&particles);
+        let approximate = tree.forces(&particles);
+        let error: f64 = exact
+            .iter()
+            .zip(&approximate)
+            .map(|(a, b)| (a - b).magnitude2())
+            .sum();
+        let total: f64 = exact.iter().map(|f| f.magnitude2()).sum();
+        assert!((error / total).sqrt() < 1e-2);
+        let exact_energy = direct.potential_energy(&particles);
+        let approximate_energy = tree.potential_energy(&particles);
+        assert!(exact_energy < 0.0);
+        assert!((exact_energy - approximate_energy).abs() < 1e-2 * exact_energy.abs());
+        let virial = tree.virial(&particles);
+        assert!((virial - direct.virial(&particles)).abs() < 2e-2 * virial.abs());
+    }
+
+    #[test]
+    fn orbits_stay_circular() {
+        for scene in [
+            crate::scene::planetary_orbits(),
+            crate::scene::binary_stars(),
+        ] {
+            let mut simulation = crate::simulation::Simulation::new(scene.particles, vec![]);
+            simulation.force_field.gravity = scene.gravity;
+            // from the centre of mass, which both scenes put at the origin
+            let distances = |particles: &ParticleStore| -> Vec<f64> {
+                particles
+                    .positions()
+                    .iter()
+                    .map(|&p| (p - cgmath::point2(0.0, 0.0)).magnitude())
+                    .collect()
+            };
+            let initial = distances(&simulation.particles);
+            for _ in 0..600 {
+                simulation.step(1.0 / 60.0);
+                let distances = distances(&simulation.particles);
+                // leaving out the sun, which only wobbles, and allowing for the pull of the
+                // binary changing a little as it turns
+                for (distance, &initial) in distances.iter().zip(&initial) {
+                    if initial > 1.0 {
+                        assert!((distance / initial - 1.0).abs() < 0.05, "{}", scene.name);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn periodic_coulomb_matches_direct_for_an_isolated_cluster() {
+        // a small neutral cluster in a big box hardly notices its images
+        let particles: ParticleStore = (0..30)
+            .map(|i| {
+                let angle = i as f64 * 2.4;
+                let radius = (i as f64).sqrt() * 0.5;
+                Particle::new(
+                    cgmath::point2(radius * angle.cos(), radius * angle.sin()),
+                    cgmath::vec2(0.0, 0.0),
+                    1.0,
+                    if i % 2 == 0 { -1.0 } else { 1.0 },
+                )
+            })
+            .collect();
+        let periodic_box = PeriodicBox::centered(cgmath::vec2(40.0, 40.0));
+        let split = EwaldSplit {
+            alpha: 0.8,
+            real_cutoff: 6.0,
+        };
+        let periodic = |summation| ForceField {
+            periodic_box: Some(periodic_box),
+            ..ForceField::new(vec![Box::new(Coulomb {
+                summation,
+                ..Coulomb::default()
+            })])
+        };
+        let mut direct = ForceField::new(vec![Box::new(Coulomb::default())]);
+        let mut ewald = periodic(Summation::Ewald { split, k_max: 40 });
+        let mut mesh = periodic(Summation::ParticleMesh { split, mesh: 64 });
+        let exact = direct.forces(&particles);
+        let total: f64 = exact.iter().map(|f| f.magnitude2()).sum();
+        for (field, tolerance) in [(&mut ewald, 1e-3), (&mut mesh, 1e-2)].iter_mut() {
+            let error: f64 = exact
+                .iter()
+                .zip(field.forces(&particles))
+                .map(|(a, b)| (a - b).magnitude2())
+                .sum();
+            assert!(
+                (error / total).sqrt() < *tolerance,
+                "{}",
+                (error / total).sqrt()
+            );
+            let exact_energy = direct.potential_energy(&particles);
+            let energy = field.potential_energy(&particles);
+            assert!((energy - exact_energy).abs() < *tolerance * exact_energy.abs());
+        }
+    }
+
+    #[test]
+    fn virial_is_the_energy_change_under_expansion() {
+        let periodic_box = PeriodicBox::centered(cgmath::vec2(11.0, 11.0));
+        let particles: ParticleStore = (0..100)
+            .map(|i| {
+                let jitter = ((i * 7919) % 17) as f64 * 0.02;
+                Particle::new(
+                    cgmath::point2(
+                        (i % 10) as f64 * 1.1 - 5.0 + jitter,
+                        (i / 10) as f64 * 1.1 - 5.0,
+                    ),
+                    cgmath::vec2(0.0, 0.0),
+                    1.0,
+                    if (i + i / 10) % 2 == 0 { 1.0 } else { -1.0 },
+                )
+            })
+            .collect();
+        let ewald = Coulomb {
+            softening: 0.0,
+            summation: Summation::Ewald {
+                split: EwaldSplit {
+                    alpha: 1.0,
+                    real_cutoff: 5.0,
+                },
+                k_max: 16,
+            },
+            ..Coulomb::default()
+        };
+        let potentials: Vec<Box<dyn PairPotential>> =
+            vec![Box::new(LennardJones::default()), Box::new(ewald)];
+        for potential in potentials {
+            let field = ForceField {
+                periodic_box: Some(periodic_box),
+                ..ForceField::new(vec![potential])
+            };
+            // scaling every length by s changes the energy by -virial * ds
+            let h = 1e-5;
+            let energy_at = |s: f64| {
+                let mut particles = particles.clone();
+                particles.move_all(|_, p| cgmath::point2(p.x * s, p.y * s));
+                let periodic_box = PeriodicBox::centered(periodic_box.size * s);
+                let context = ForceContext {
+                    particles: &particles,
+                    neighbors: None,
+                    periodic_box: Some(&periodic_box),
+                    parallel: false,
+                };
+                field.potentials[0].total_energy(&context)
+            };
+            let numeric = -(energy_at(1.0 + h) - energy_at(1.0 - h)) / (2.0 * h);
+            let virial = field.virial(&particles);
+            assert!(
+                (virial - numeric).abs() < 1e-5 * (1.0 + numeric.abs()),
+                "{} {}",
+                virial,
+                numeric
+            );
+        }
+    }
+
+    #[test]
+    fn neighbor_list_matches_direct_summation() {
+        let particles: ParticleStore = (0..300)
+            .map(|i| {
+                let jitter = ((i * 7919) % 17) as f64 * 0.02;
+                Particle::new(
+                    cgmath::point2((i % 15) as f64 * 1.1 + jitter, (i / 15) as f64 * 1.1),
+                    cgmath::vec2(0.0, 0.0),
+                    1.0,
+                    0.0,
+                )
+            })
+            .collect();
+        let potentials = || -> Vec<Box<dyn PairPotential>> {
+            vec![
+                Box::new(LennardJones::default()),
+                Box::new(SoftSphere {
+                    exponent: 6,
+                    cutoff: 1.5,
+                }),
+            ]
+        };
+        let mut listed = ForceField::new(potentials());
+        let mut direct = ForceField {
+            neighbor_list: None,
+            ..ForceField::new(potentials())
+        };
+        for (a, b) in listed
+            .forces(&particles)
+            .iter()
+            .zip(direct.forces(&particles))
+        {
+            assert!((a - b).magnitude() < 1e-12);
+        }
+        let listed_energy = listed.potential_energy(&particles);
+        assert!((listed_energy - direct.potential_energy(&particles)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parallel_forces_do_not_depend_on_thread_count() {
+        let particles: ParticleStore = (0..400)
+            .map(|i| {
+                let jitter = ((i * 7919) % 17) as f64 * 0.02;
+                Particle::new(
+                    cgmath::point2((i % 20) as f64 * 1.1 + jitter, (i / 20) as f64 * 1.1),
+                    cgmath::vec2(0.0, 0.0),
+                    1.0,
+                    if i % 3 == 0 { 1.0 } else { -0.5 },
+                )
+            })
+            .collect();
+        let fields = || -> Vec<ForceField> {
+            let parallel = |potentials: Vec<Box<dyn PairPotential>>| ForceField {
+                parallel: true,
+                ..ForceField::new(potentials)
+            };
+            vec![
+                // through the neighbour list
+                parallel(vec![Box::new(LennardJones::default())]),
+                // every pair
+                parallel(all_potentials()),
+                parallel(vec![Box::new(Coulomb {
+                    summation: Summation::BarnesHut { theta: 0.5 },
+                    ..Coulomb::default()
+                })]),
+            ]
+        };
+        let on_threads = |threads: usize| -> Vec<Vec<cgmath::Vector2<f64>>> {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| fields().iter_mut().map(|f| f.forces(&particles)).collect())
+        };
+        let single = on_threads(1);
+        for threads in [2, 3, 8] {
+            assert_eq!(on_threads(threads), single);
+        }
+
+        let serial = fields().into_iter().map(|mut field| {
+            field.parallel = false;
+            field.forces(&particles)
+        });
+        for (serial, parallel) in serial.zip(&single) {
+            for (a, b) in serial.iter().zip(parallel) {
+                assert!((a - b).magnitude() < 1e-9 * (1.0 + a.magnitude()));
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashSet;
+
+use crate::{
+    dimension::Vector,
+    particle::ParticleId,
+    potential::ForceContext,
+    topology::{Bond, BondKind, Topology},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondEventKind {
+    Formed,
+    Broken,
+}
+
+/// A bond that formed or broke during a step, with where it happened for the UI to show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BondEvent<V: Vector = cgmath::Vector2<f64>> {
+    pub kind: BondEventKind,
+    pub a: ParticleId,
+    pub b: ParticleId,
+    /// Midpoint of the bond.
+    pub position: V::Point,
+}
+
+/// Rules for bonds forming and breaking on their own. Each particle can hold as many bonds as
+/// its valence, counting the ones it started with, so particles with no valence never react.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reactions {
+    /// Two particles with valence to spare bond when they come this close...
+    pub capture_distance: f64,
+    /// ...and the kinetic energy of their motion relative to each other is below this, so fast
+    /// collisions just bounce off.
+    pub max_collision_energy: f64,
+    /// Any bond stretched further than this breaks, along with the angles built on it.
+    pub break_length: f64,
+    /// The spring new bonds are made of.
+    pub bond: BondKind,
+}
+
+impl Reactions {
+    /// Breaks overstretched bonds, then forms new ones, closest pairs first. Candidates come
+    /// from `context`, which should find every pair within the capture distance.
+    pub fn apply<V: Vector>(
+        &self,
+        context: &ForceContext<V>,
+        topology: &mut Topology,
+    ) -> Vec<BondEvent<V>> {
+        let particles = context.particles;
+        let event = |kind, i: usize, j: usize| BondEvent {
+            kind,
+            a: particles.ids()[i],
+            b: particles.ids()[j],
+            position: particles.positions()[j] + context.displacement(i, j) / 2.0,
+        };
+        let mut events = vec![];
+
+        let mut ends = topology.bond_ends(particles).into_iter();
+        let mut broken = HashSet::new();
+        let mut bonds_held = vec![0; particles.len()];
+        let mut bonded = HashSet::new();
+        topology.bonds.retain(|bond| {
+            let (i, j) = match ends.next().flatten() {
+                Some(ends) => ends,
+                None => return true,
+            };
+            if context.displacement(i, j).magnitude() > self.break_length {
+                events.push(event(BondEventKind::Broken, i, j));
+                broken.insert(pair(bond.a, bond.b));
+                return false;
+            }
+            bonds_held[i] += 1;
+            bonds_held[j] += 1;
+            bonded.insert((i.min(j), i.max(j)));
+            true
+        });
+        if !broken.is_empty() {
+            topology.angles.retain(|angle| {
+                !broken.contains(&pair(angle.a, angle.vertex))
+                    && !broken.contains(&pair(angle.b, angle.vertex))
+            });
+        }
+
+        let free = |bonds_held: &[u32], i: usize| particles.valences()[i] > bonds_held[i];
+        let mut candidates = vec![];
+        context.for_each_pair(Some(self.capture_distance), |i, j| {
+            if !free(&bonds_held, i) || !free(&bonds_held, j) || bonded.contains(&(i, j)) {
+                return;
+            }
+            let distance = context.displacement(i, j).magnitude();
+            if distance > self.capture_distance {
+                return;
+            }
+            let (a, b) = (particles.get(i), particles.get(j));
+            let reduced_mass = a.mass() * b.mass() / (a.mass() + b.mass());
+            let energy = 0.5 * reduced_mass * (a.velocity() - b.velocity()).magnitude2();
+            if energy < self.max_collision_energy {
+                candidates.push((distance, i, j));
+            }
+        });
+        // pairs come in order of their indices and the sort is stable, so ties keep that order
+        // and runs stay reproducible
+        candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        for (_, i, j) in candidates {
+            if !free(&bonds_held, i) || !free(&bonds_held, j) {
+                continue;
+            }
+            bonds_held[i] += 1;
+            bonds_held[j] += 1;
+            topology.bonds.push(Bond {
+                a: particles.ids()[i],
+                b: particles.ids()[j],
+                kind: self.bond,
+            });
+            events.push(event(BondEventKind::Formed, i, j));
+        }
+        events
+    }
+}
+
+/// The ids of a bond's ends in a fixed order, so either direction matches.
+fn pair(a: ParticleId, b: ParticleId) -> (ParticleId, ParticleId) {
+    (a.min(b), a.max(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        particle::{Particle, ParticleStore},
+        species::{Species, SpeciesId},
+    };
+    use cgmath::InnerSpace;
+
+    fn reactions() -> Reactions {
+        Reactions {
+            capture_distance: 1.3,
+            max_collision_energy: 0.5,
+            break_length: 2.0,
+            bond: BondKind::Harmonic {
+                stiffness: 20.0,
+                rest_length: 1.1,
+            },
+        }
+    }
+
+    /// Every pair is a candidate, as there's no neighbour list.
+    fn apply(particles: &ParticleStore, topology: &mut Topology) -> Vec<BondEvent> {
+        let context = ForceContext {
+            particles,
+            neighbors: None,
+            periodic_box: None,
+            parallel: false,
+        };
+        reactions().apply(&context, topology)
+    }
+
+    fn atom(x: f64, vx: f64, valence: u32) -> Particle {
+        let species = Species::new("atom", 1.0, 0.0, [1.0, 1.0, 1.0]).with_valence(valence);
+        Particle::of_species(
+            SpeciesId::NEUTRAL,
+            &species,
+            cgmath::point2(x, 0.0),
+            cgmath::vec2(vx, 0.0),
+        )
+    }
+
+    #[test]
+    fn slow_neighbours_bond() {
+        let particles: ParticleStore = vec![atom(0.0, 0.2, 1), atom(1.2, -0.2, 1)]
+            .into_iter()
+            .collect();
+        let mut topology = Topology::default();
+        let events = apply(&particles, &mut topology);
+        assert_eq!(topology.bonds.len(), 1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, BondEventKind::Formed);
+        assert_eq!(
+            (events[0].a, events[0].b),
+            (particles.ids()[0], particles.ids()[1])
+        );
+        assert!((events[0].position - cgmath::point2(0.6, 0.0)).magnitude() < 1e-12);
+
+        // and don't bond a second time
+        let events = apply(&particles, &mut topology);
+        assert!(events.is_empty());
+        assert_eq!(topology.bonds.len(), 1);
+    }
+
+    #[test]
+    fn fast_collisions_and_inert_particles_dont_bond() {
+        let mut topology = Topology::default();
+        let fast: ParticleStore = vec![atom(0.0, 1.0, 1), atom(1.2, -1.0, 1)]
+            .into_iter()
+            .collect();
+        assert!(apply(&fast, &mut topology).is_empty());
+        let inert: ParticleStore = vec![atom(0.0, 0.0, 1), atom(1.2, 0.0, 0)]
+            .into_iter()
+            .collect();
+        assert!(apply(&inert, &mut topology).is_empty());
+        assert!(topology.bonds.is_empty());
+    }
+
+    #[test]
+    fn valence_limits_the_bonds() {
+        // the middle atom can only take one partner, and takes the closer one
+        let particles: ParticleStore =
+            vec![atom(-1.2, 0.0, 1), atom(0.0, 0.0, 1), atom(1.1, 0.0, 1)]
+                .into_iter()
+                .collect();
+        let mut topology = Topology::default();
+        apply(&particles, &mut topology);
+        assert_eq!(topology.bonds.len(), 1);
+        assert_eq!(
+            pair(topology.bonds[0].a, topology.bonds[0].b),
+            pair(particles.ids()[1], particles.ids()[2])
+        );
+
+        // with a valence of two it takes both
+        let particles: ParticleStore =
+            vec![atom(-1.2, 0.0, 1), atom(0.0, 0.0, 2), atom(1.1, 0.0, 1)]
+                .into_iter()
+                .collect();
+        let mut topology = Topology::default();
+        apply(&particles, &mut topology);
+        assert_eq!(topology.bonds.len(), 2);
+    }
+
+    #[test]
+    fn overstretched_bonds_break() {
+        let mut particles: ParticleStore = vec![atom(0.0, 0.0, 1), atom(1.1, 0.0, 1)]
+            .into_iter()
+            .collect();
+        let mut topology = Topology::default();
+        apply(&particles, &mut topology);
+        particles.get_mut(1).set_position(cgmath::point2(2.5, 0.0));
+        let events = apply(&particles, &mut topology);
+        assert!(topology.bonds.is_empty());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, BondEventKind::Broken);
+    }
+
+    #[test]
+    fn simulation_reports_bond_events() {
+        let particles = vec![atom(0.0, 0.1, 1), atom(1.25, -0.1, 1)];
+        let mut simulation = crate::simulation::Simulation::new(particles, vec![]);
+        simulation.reactions = Some(reactions());
+        simulation.step(0.01);
+        let events = simulation.take_bond_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(simulation.force_field.topology.bonds.len(), 1);
+        assert!(simulation.take_bond_events().is_empty());
+
+        // the new bond pulls them together and they oscillate without breaking
+        for _ in 0..1000 {
+            simulation.step(0.01);
+        }
+        assert!(simulation.take_bond_events().is_empty());
+    }
+
+    #[test]
+    fn neighbour_list_finds_the_same_bonds() {
+        // a loose grid of atoms, close enough in places to bond
+        let particles: Vec<_> = (0..64)
+            .map(|i| {
+                let mut atom = atom(0.0, 0.0, 2);
+                let jitter = ((i * 7919) % 13) as f64 * 0.03;
+                atom.set_position(cgmath::point2(
+                    (i % 8) as f64 * 1.4 + jitter,
+                    (i / 8) as f64 * 1.4 - jitter,
+                ));
+                atom
+            })
+            .collect();
+        let bonds = |neighbor_list: bool| {
+            let mut simulation = crate::simulation::Simulation::new(
+                particles.clone(),
+                vec![Box::new(crate::potential::LennardJones::default())],
+            );
+            if !neighbor_list {
+                simulation.force_field.neighbor_list = None;
+            }
+            simulation.reactions = Some(reactions());
+            simulation.step(0.01);
+            simulation.force_field.topology.bonds
+        };
+        let listed = bonds(true);
+        assert!(!listed.is_empty());
+        assert_eq!(listed, bonds(false));
+    }
+}
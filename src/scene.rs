@@ -0,0 +1,829 @@
+use cgmath::{EuclideanSpace, InnerSpace};
+
+use crate::{
+    boundary::PeriodicBox,
+    collision::Collisions,
+    dimension::Vector,
+    external_field::ExternalField,
+    external_potential::{ExternalPotential, PotentialMap},
+    particle::{LennardJonesParameters, Particle},
+    potential::{Gravity, Summation},
+    reaction::Reactions,
+    species::{DisplayScale, Species, SpeciesId, SpeciesTable},
+    topology::{Angle, Bond, BondKind, Topology},
+    wall::{Wall, WallKind},
+};
+
+/// Particles per side of the lattice the periodic scenes start from.
+const LATTICE_SIDE: usize = 12;
+const LATTICE_SPACING: f64 = 1.5;
+
+pub struct Scene<V: Vector = cgmath::Vector2<f64>> {
+    pub name: &'static str,
+    pub species: SpeciesTable,
+    pub particles: Vec<Particle<V>>,
+    pub periodic_box: Option<PeriodicBox<V>>,
+    pub walls: Vec<Wall>,
+    pub topology: Topology,
+    pub collisions: Option<Collisions>,
+    pub reactions: Option<Reactions>,
+    pub external_field: ExternalField<V>,
+    pub external_potentials: Vec<ExternalPotential<V>>,
+    pub gravity: Option<Gravity>,
+}
+
+impl<V: Vector> Scene<V> {
+    /// Particles in open space, with no walls, bonds, collisions, reactions, external fields or
+    /// gravity.
+    fn new(name: &'static str, species: SpeciesTable, particles: Vec<Particle<V>>) -> Self {
+        Self {
+            name,
+            species,
+            particles,
+            periodic_box: None,
+            walls: vec![],
+            topology: Topology::default(),
+            collisions: None,
+            reactions: None,
+            external_field: ExternalField::default(),
+            external_potentials: vec![],
+            gravity: None,
+        }
+    }
+}
+
+/// The scenes there are to cycle through in each dimension.
+pub trait Catalog: Vector {
+    /// `potential_map` is the landscape for the scene that shows one off, or `None` for the
+    /// built-in one.
+    fn scenes(potential_map: Option<&PotentialMap>) -> Vec<Scene<Self>>;
+}
+
+impl Catalog for cgmath::Vector2<f64> {
+    fn scenes(potential_map: Option<&PotentialMap>) -> Vec<Scene> {
+        vec![
+            colliding_pair(),
+            periodic_gas(),
+            ionic_crystal(),
+            heated_container(),
+            water(),
+            polymer_chain(),
+            reactive_gas(),
+            billiards(),
+            granular_gas(),
+            cyclotron_orbits(),
+            e_cross_b_drift(),
+            harmonic_trap(),
+            optical_lattice(),
+            gravity_well(),
+            potential_landscape(potential_map.cloned()),
+            planetary_orbits(),
+            binary_stars(),
+            galaxy_collision(),
+        ]
+    }
+}
+
+impl Catalog for cgmath::Vector3<f64> {
+    /// There's no landscape to show off in 3D.
+    fn scenes(_: Option<&PotentialMap>) -> Vec<Scene<Self>> {
+        vec![
+            droplet(),
+            periodic_gas_3d(),
+            rock_salt(),
+            polymer_coil(),
+            cyclotron_helices(),
+            inclined_orbits(),
+        ]
+    }
+}
+
+fn periodic_box() -> Option<PeriodicBox> {
+    Some(PeriodicBox::centered(cgmath::vec2(18.0, 18.0)))
+}
+
+/// A positive and a negative charge aimed just past each other.
+pub fn colliding_pair() -> Scene {
+    let mut species = SpeciesTable::default();
+    let small_anion =
+        species.add(Species::new("small anion", 1.0, -1.0, [0.286, 0.322, 1.0]).with_radius(0.4));
+    let particles = vec![
+        species.spawn(
+            SpeciesId::CATION,
+            cgmath::Point2::new(-6.0, 0.75),
+            cgmath::Vector2::new(1.0, 0.0),
+        ),
+        species.spawn(
+            small_anion,
+            cgmath::Point2::new(6.0, -0.75),
+            cgmath::Vector2::new(-1.0, 0.0),
+        ),
+    ];
+    Scene::new("colliding pair", species, particles)
+}
+
+/// A 12 by 12 lattice filling an 18 by 18 box, with scrambled velocities that add up to zero
+/// momentum. `species_at` picks the species at each column and row.
+fn lattice(
+    species: &SpeciesTable,
+    species_at: impl Fn(usize, usize) -> SpeciesId,
+    speed: f64,
+) -> Vec<Particle> {
+    let velocity = |i: usize| {
+        let angle = (i * 7919 % 360) as f64 * std::f64::consts::PI / 180.0;
+        cgmath::Vector2::new(angle.cos(), angle.sin()) * speed
+    };
+    let mean = (0..LATTICE_SIDE * LATTICE_SIDE)
+        .map(velocity)
+        .sum::<cgmath::Vector2<f64>>()
+        / (LATTICE_SIDE * LATTICE_SIDE) as f64;
+    (0..LATTICE_SIDE * LATTICE_SIDE)
+        .map(|i| {
+            let (x, y) = (i % LATTICE_SIDE, i / LATTICE_SIDE);
+            let position = cgmath::Point2::new(
+                (x as f64 + 0.5) * LATTICE_SPACING - 9.0,
+                (y as f64 + 0.5) * LATTICE_SPACING - 9.0,
+            );
+            species.spawn(species_at(x, y), position, velocity(i) - mean)
+        })
+        .collect()
+}
+
+/// Neutral Lennard-Jones particles filling a periodic box.
+pub fn periodic_gas() -> Scene {
+    let species = SpeciesTable::default();
+    let particles = lattice(&species, |_, _| SpeciesId::NEUTRAL, 0.8);
+    let mut scene = Scene::new("periodic gas", species, particles);
+    scene.periodic_box = periodic_box();
+    scene
+}
+
+/// The same lattice at rest with alternating charges, like a layer of rock salt.
+pub fn ionic_crystal() -> Scene {
+    let species = SpeciesTable::default();
+    let particles = lattice(
+        &species,
+        |x, y| {
+            if (x + y) % 2 == 0 {
+                SpeciesId::CATION
+            } else {
+                SpeciesId::ANION
+            }
+        },
+        0.0,
+    );
+    let mut scene = Scene::new("ionic crystal", species, particles);
+    scene.periodic_box = periodic_box();
+    scene
+}
+
+/// The periodic gas with a third of the particles able to take two bonds and the rest able to
+/// take one, so they react into small molecules until heated back apart.
+pub fn reactive_gas() -> Scene {
+    let mut species = SpeciesTable::default();
+    let divalent = species.add(Species::new("divalent", 1.0, 0.0, [1.0, 0.7, 0.1]).with_valence(2));
+    let monovalent =
+        species.add(Species::new("monovalent", 1.0, 0.0, [0.4, 0.8, 1.0]).with_valence(1));
+    let particles = lattice(
+        &species,
+        |x, y| match (x + y * LATTICE_SIDE) % 3 {
+            0 => divalent,
+            _ => monovalent,
+        },
+        0.8,
+    );
+    let mut scene = Scene::new("reactive gas", species, particles);
+    scene.periodic_box = periodic_box();
+    scene.reactions = Some(Reactions {
+        capture_distance: 1.3,
+        max_collision_energy: 0.4,
+        break_length: 1.8,
+        bond: BondKind::Harmonic {
+            stiffness: 20.0,
+            rest_length: 1.12,
+        },
+    });
+    scene
+}
+
+/// A species of hard balls that only interact by colliding: with no Lennard-Jones well and no
+/// charge, none of the physics models act on them.
+fn ball(name: &'static str, color: [f32; 3]) -> Species {
+    Species::new(name, 1.0, 0.0, color).with_lennard_jones(LennardJonesParameters {
+        epsilon: 0.0,
+        sigma: 1.0,
+    })
+}
+
+/// A rack of fifteen balls broken by a cue ball on a walled table, in perfectly elastic
+/// collisions.
+pub fn billiards() -> Scene {
+    let mut species = SpeciesTable::default();
+    let cue = species.add(ball("cue ball", [1.0, 1.0, 0.9]));
+    let object = species.add(ball("ball", [0.9, 0.2, 0.1]));
+    let at_rest = cgmath::Vector2::new(0.0, 0.0);
+    let mut particles = vec![species.spawn(
+        cue,
+        cgmath::Point2::new(-8.0, 0.05),
+        cgmath::Vector2::new(8.0, 0.0),
+    )];
+    // rows of one to five touching balls, each row nestled into the gaps of the one before
+    let row_spacing = 3.0f64.sqrt() / 2.0;
+    for row in 0..5 {
+        for n in 0..=row {
+            let position = cgmath::Point2::new(
+                4.0 + row as f64 * row_spacing,
+                (n as f64 - row as f64 / 2.0) * 1.0,
+            );
+            particles.push(species.spawn(object, position, at_rest));
+        }
+    }
+    let mut scene = Scene::new("billiards", species, particles);
+    scene.walls = Wall::rectangle(
+        cgmath::Point2::new(-12.0, -6.0),
+        cgmath::Point2::new(12.0, 6.0),
+        WallKind::Reflective,
+    );
+    scene.collisions = Some(Collisions { restitution: 1.0 });
+    scene
+}
+
+/// Hard balls filling the periodic box that lose a fifth of their approach speed in every
+/// collision, so the gas cools and gathers into clusters.
+pub fn granular_gas() -> Scene {
+    let mut species = SpeciesTable::default();
+    let grain = species.add(ball("grain", [0.85, 0.7, 0.45]));
+    let particles = lattice(&species, |_, _| grain, 0.8);
+    let mut scene = Scene::new("granular gas", species, particles);
+    scene.periodic_box = periodic_box();
+    scene.collisions = Some(Collisions { restitution: 0.8 });
+    scene
+}
+
+/// Charges of both signs launched at different speeds across a magnetic field, circling
+/// opposite ways on orbits that grow with their speed.
+pub fn cyclotron_orbits() -> Scene {
+    let species = SpeciesTable::default();
+    let particles = (0..6)
+        .map(|i| {
+            let id = [SpeciesId::CATION, SpeciesId::ANION][i % 2];
+            let position = cgmath::Point2::new((i as f64 - 2.5) * 5.0, 0.0);
+            species.spawn(
+                id,
+                position,
+                cgmath::Vector2::new(0.0, 0.5 + 0.25 * i as f64),
+            )
+        })
+        .collect();
+    let mut scene = Scene::new("cyclotron orbits", species, particles);
+    scene.external_field = ExternalField {
+        electric: cgmath::vec2(0.0, 0.0),
+        magnetic: 0.5,
+    };
+    scene
+}
+
+/// Charges starting at rest in crossed electric and magnetic fields. Each sign loops its own
+/// way, but they all drift sideways together along E x B.
+pub fn e_cross_b_drift() -> Scene {
+    let species = SpeciesTable::default();
+    let particles = (0..8)
+        .map(|i| {
+            let id = if i < 4 {
+                SpeciesId::CATION
+            } else {
+                SpeciesId::ANION
+            };
+            let position = cgmath::Point2::new(-12.0, (i as f64 - 3.5) * 3.0);
+            species.spawn(id, position, cgmath::Vector2::new(0.0, 0.0))
+        })
+        .collect();
+    let mut scene = Scene::new("e cross b drift", species, particles);
+    scene.external_field = ExternalField {
+        electric: cgmath::vec2(0.0, 0.5),
+        magnetic: 1.0,
+    };
+    scene
+}
+
+/// The neutral gas let loose in open space, held together by a harmonic trap.
+pub fn harmonic_trap() -> Scene {
+    let species = SpeciesTable::default();
+    let particles = lattice(&species, |_, _| SpeciesId::NEUTRAL, 1.5);
+    let mut scene = Scene::new("harmonic trap", species, particles);
+    scene.external_potentials = vec![ExternalPotential::Harmonic {
+        center: cgmath::Point2::new(0.0, 0.0),
+        stiffness: 0.05,
+    }];
+    scene
+}
+
+/// The periodic gas in an optical lattice of six by six wells, four particles to a well.
+pub fn optical_lattice() -> Scene {
+    let species = SpeciesTable::default();
+    let particles = lattice(&species, |_, _| SpeciesId::NEUTRAL, 0.8);
+    let mut scene = Scene::new("optical lattice", species, particles);
+    scene.periodic_box = periodic_box();
+    scene.external_potentials = vec![ExternalPotential::OpticalLattice {
+        depth: 1.5,
+        spacing: 3.0,
+    }];
+    scene
+}
+
+/// Three rings of particles on circular orbits around a gravity well, the inner ones lapping
+/// the outer ones.
+pub fn gravity_well() -> Scene {
+    let (strength, softening): (f64, f64) = (40.0, 0.5);
+    let species = SpeciesTable::default();
+    let mut particles = vec![];
+    for &(radius, count) in &[(4.0, 12), (7.0, 20), (10.0, 28)] {
+        // fast enough for the pull of the softened well to bend the path into a circle
+        let speed = (strength * radius * radius).sqrt()
+            / (radius * radius + softening * softening).powf(0.75);
+        for i in 0..count {
+            let angle = 2.0 * std::f64::consts::PI * i as f64 / count as f64;
+            let (sin, cos) = angle.sin_cos();
+            particles.push(species.spawn(
+                SpeciesId::NEUTRAL,
+                cgmath::Point2::new(cos, sin) * radius,
+                cgmath::Vector2::new(-sin, cos) * speed,
+            ));
+        }
+    }
+    let mut scene = Scene::new("gravity well", species, particles);
+    scene.external_potentials = vec![ExternalPotential::GravityWell {
+        center: cgmath::Point2::new(0.0, 0.0),
+        strength,
+        softening,
+    }];
+    scene
+}
+
+/// Where `potential_landscape` stretches its map.
+pub const LANDSCAPE_MIN: cgmath::Point2<f64> = cgmath::Point2::new(-15.0, -15.0);
+pub const LANDSCAPE_MAX: cgmath::Point2<f64> = cgmath::Point2::new(15.0, 15.0);
+/// How much higher white is than black in `potential_landscape`.
+pub const LANDSCAPE_DEPTH: f64 = 3.0;
+
+/// The neutral gas settling into the valleys of `map`, or else of a circular moat.
+pub fn potential_landscape(map: Option<PotentialMap>) -> Scene {
+    let map = map.unwrap_or_else(|| {
+        let size = 64;
+        let values = (0..size * size)
+            .map(|i| {
+                let cell = (LANDSCAPE_MAX.x - LANDSCAPE_MIN.x) / (size - 1) as f64;
+                let x = LANDSCAPE_MIN.x + (i % size) as f64 * cell;
+                let y = LANDSCAPE_MIN.y + (i / size) as f64 * cell;
+                let from_moat = ((x * x + y * y).sqrt() - 9.0) / 2.5;
+                LANDSCAPE_DEPTH * (1.0 - (-from_moat * from_moat).exp())
+            })
+            .collect();
+        PotentialMap::new(values, size, LANDSCAPE_MIN, LANDSCAPE_MAX)
+    });
+    let species = SpeciesTable::default();
+    let particles = lattice(&species, |_, _| SpeciesId::NEUTRAL, 0.8);
+    let mut scene = Scene::new("potential landscape", species, particles);
+    scene.external_potentials = vec![ExternalPotential::Map(map)];
+    scene
+}
+
+/// Speed of a circular orbit of `radius` around `mass` pulled in by softened `gravity`.
+fn orbital_speed(gravity: &Gravity, mass: f64, radius: f64) -> f64 {
+    let d2 = radius * radius + gravity.softening * gravity.softening;
+    (gravity.g * mass * radius * radius).sqrt() / d2.powf(0.75)
+}
+
+/// `count` bodies of species `id` on circular orbits around `central`, moving along with it.
+/// Their radii run from `min_radius` to `max_radius` so the disk is evenly covered, and they
+/// all go round anticlockwise.
+fn disk(
+    species: &SpeciesTable,
+    id: SpeciesId,
+    gravity: &Gravity,
+    central: &Particle,
+    (min_radius, max_radius): (f64, f64),
+    count: usize,
+) -> Vec<Particle> {
+    (0..count)
+        .map(|i| {
+            let fraction = (i as f64 + 0.5) / count as f64;
+            let radius = (min_radius * min_radius
+                + fraction * (max_radius * max_radius - min_radius * min_radius))
+                .sqrt();
+            // a golden angle apart, so no two bodies line up
+            let (sin, cos) = (i as f64 * GOLDEN_ANGLE).sin_cos();
+            let speed = orbital_speed(gravity, central.mass(), radius);
+            species.spawn(
+                id,
+                central.position() + cgmath::Vector2::new(cos, sin) * radius,
+                central.velocity() + cgmath::Vector2::new(-sin, cos) * speed,
+            )
+        })
+        .collect()
+}
+
+const GOLDEN_ANGLE: f64 = 2.39996;
+
+/// Planets on circular orbits around a sun a thousand times heavier.
+pub fn planetary_orbits() -> Scene {
+    let gravity = Gravity {
+        softening: 0.1,
+        ..Gravity::default()
+    };
+    let mut species = SpeciesTable::default();
+    let sun = species.add(Species::new("sun", 100.0, 0.0, [1.0, 0.8, 0.3]).with_radius(1.0));
+    let planet = species.add(Species::new("planet", 0.1, 0.0, [0.3, 0.6, 1.0]).with_radius(0.3));
+    let mut particles = vec![species.spawn(
+        sun,
+        cgmath::Point2::new(0.0, 0.0),
+        cgmath::Vector2::new(0.0, 0.0),
+    )];
+    for (i, &radius) in [4.0, 6.5, 9.0, 12.5, 16.0].iter().enumerate() {
+        let (sin, cos) = (i as f64 * GOLDEN_ANGLE).sin_cos();
+        let speed = orbital_speed(&gravity, 100.0, radius);
+        particles.push(species.spawn(
+            planet,
+            cgmath::Point2::new(cos, sin) * radius,
+            cgmath::Vector2::new(-sin, cos) * speed,
+        ));
+    }
+    // the sun moves against the planets, so the system as a whole stays put
+    let momentum: cgmath::Vector2<f64> = particles.iter().map(|p| p.velocity() * p.mass()).sum();
+    particles[0].kick(-momentum / 100.0);
+    let mut scene = Scene::new("planetary orbits", species, particles);
+    scene.gravity = Some(gravity);
+    scene
+}
+
+/// Two equal stars circling each other, with planets orbiting the pair from further out.
+pub fn binary_stars() -> Scene {
+    let gravity = Gravity {
+        softening: 0.1,
+        ..Gravity::default()
+    };
+    let (star_mass, separation) = (50.0, 6.0);
+    let mut species = SpeciesTable::default();
+    let star = species.add(Species::new("star", star_mass, 0.0, [1.0, 0.85, 0.5]).with_radius(0.8));
+    let planet = species.add(Species::new("planet", 0.1, 0.0, [0.3, 0.6, 1.0]).with_radius(0.3));
+    // each star goes round the middle at half the separation, pulled by the other from the
+    // whole of it, which takes half the squared speed of orbiting a fixed star that far out
+    let speed = orbital_speed(&gravity, star_mass, separation) / 2f64.sqrt();
+    let mut particles = vec![
+        species.spawn(
+            star,
+            cgmath::Point2::new(-separation / 2.0, 0.0),
+            cgmath::Vector2::new(0.0, -speed),
+        ),
+        species.spawn(
+            star,
+            cgmath::Point2::new(separation / 2.0, 0.0),
+            cgmath::Vector2::new(0.0, speed),
+        ),
+    ];
+    // from far enough away the pair pulls like a single star as heavy as both
+    let pair = Particle::new(
+        cgmath::Point2::new(0.0, 0.0),
+        cgmath::Vector2::new(0.0, 0.0),
+        2.0 * star_mass,
+        0.0,
+    );
+    particles.extend(disk(&species, planet, &gravity, &pair, (16.0, 22.0), 6));
+    let mut scene = Scene::new("binary stars", species, particles);
+    scene.gravity = Some(gravity);
+    scene
+}
+
+/// Two disk galaxies, each a heavy core with light stars going round it, falling past each
+/// other. Tidal forces pull long tails out of both.
+pub fn galaxy_collision() -> Scene {
+    let gravity = Gravity {
+        softening: 0.3,
+        summation: Summation::BarnesHut { theta: 0.5 },
+        ..Gravity::default()
+    };
+    let core_mass = 60.0;
+    let mut species = SpeciesTable::default();
+    let core = species.add(Species::new("core", core_mass, 0.0, [1.0, 0.9, 0.7]).with_radius(0.6));
+    // stars are too light to shrug off a Lennard-Jones well, so they have none
+    let star = species.add(
+        Species::new("star", 0.01, 0.0, [0.75, 0.8, 1.0])
+            .with_lennard_jones(LennardJonesParameters {
+                epsilon: 0.0,
+                sigma: 0.2,
+            })
+            .with_display_scale(DisplayScale::ConstantPixels(4.0)),
+    );
+    let mut particles = vec![];
+    for &(x, y, vx, vy) in &[(-12.0, -4.0, 1.2, 0.3), (12.0, 4.0, -1.2, -0.3)] {
+        let core = species.spawn(
+            core,
+            cgmath::Point2::new(x, y),
+            cgmath::Vector2::new(vx, vy),
+        );
+        particles.extend(disk(&species, star, &gravity, &core, (1.5, 6.0), 150));
+        particles.push(core);
+    }
+    let mut scene = Scene::new("galaxy collision", species, particles);
+    scene.gravity = Some(gravity);
+    scene
+}
+
+/// The neutral gas in a box with a hot floor, a slanted baffle, and a gap in the lid that lets
+/// particles escape.
+pub fn heated_container() -> Scene {
+    let species = SpeciesTable::default();
+    let particles = lattice(&species, |_, _| SpeciesId::NEUTRAL, 0.8);
+    let mut scene = Scene::new("heated container", species, particles);
+    scene.walls = container_walls();
+    scene
+}
+
+fn container_walls() -> Vec<Wall> {
+    let (min, max) = (-9.5, 9.5);
+    let mut walls = Wall::rectangle(
+        cgmath::Point2::new(min, min),
+        cgmath::Point2::new(max, max),
+        WallKind::Reflective,
+    );
+    walls[0].kind = WallKind::Thermal { temperature: 2.0 };
+    // swap the lid for two halves with an absorbing gap between them
+    walls.remove(2);
+    walls.push(Wall::new(
+        cgmath::Point2::new(max, max),
+        cgmath::Point2::new(1.0, max),
+        WallKind::Reflective,
+    ));
+    walls.push(Wall::new(
+        cgmath::Point2::new(1.0, max),
+        cgmath::Point2::new(-1.0, max),
+        WallKind::Absorbing,
+    ));
+    walls.push(Wall::new(
+        cgmath::Point2::new(-1.0, max),
+        cgmath::Point2::new(min, max),
+        WallKind::Reflective,
+    ));
+    walls.push(Wall::new(
+        cgmath::Point2::new(-9.5, -3.0),
+        cgmath::Point2::new(-4.0, -6.0),
+        WallKind::Reflective,
+    ));
+    walls
+}
+
+/// A 6 by 6 lattice of bent three-site water molecules in the same periodic box, with a
+/// negative oxygen held to two positive hydrogens by stiff bonds and a 104.5 degree angle.
+pub fn water() -> Scene {
+    const SIDE: usize = 6;
+    const SPACING: f64 = 3.0;
+    let bond = BondKind::Harmonic {
+        stiffness: 200.0,
+        rest_length: 1.0,
+    };
+    let rest_angle = 104.5f64.to_radians();
+    let mut species = SpeciesTable::default();
+    let oxygen = species.add(Species::new("oxygen", 4.0, -0.8, [1.0, 0.25, 0.2]));
+    // hydrogens are tiny, so they're drawn at a fixed size to stay visible when zoomed out
+    let hydrogen = species.add(
+        Species::new("hydrogen", 1.0, 0.4, [0.85, 0.85, 0.95])
+            .with_lennard_jones(LennardJonesParameters {
+                epsilon: 0.1,
+                sigma: 0.4,
+            })
+            .with_display_scale(DisplayScale::ConstantPixels(10.0)),
+    );
+    let mut particles = vec![];
+    let mut topology = Topology::default();
+    for i in 0..SIDE * SIDE {
+        let center = cgmath::Point2::new(
+            ((i % SIDE) as f64 + 0.5) * SPACING - 9.0,
+            ((i / SIDE) as f64 + 0.5) * SPACING - 9.0,
+        );
+        // turn every molecule a different way so they don't start out lined up
+        let heading = (i * 137 % 360) as f64 * std::f64::consts::PI / 180.0;
+        let oxygen = species.spawn(oxygen, center, cgmath::Vector2::new(0.0, 0.0));
+        let hydrogens: Vec<_> = [-0.5, 0.5]
+            .iter()
+            .map(|side| {
+                let angle = heading + side * rest_angle;
+                let offset = cgmath::Vector2::new(angle.cos(), angle.sin());
+                species.spawn(hydrogen, center + offset, cgmath::Vector2::new(0.0, 0.0))
+            })
+            .collect();
+        for hydrogen in &hydrogens {
+            topology.bonds.push(Bond {
+                a: oxygen.id(),
+                b: hydrogen.id(),
+                kind: bond,
+            });
+        }
+        topology.angles.push(Angle {
+            a: hydrogens[0].id(),
+            vertex: oxygen.id(),
+            b: hydrogens[1].id(),
+            stiffness: 50.0,
+            rest_angle,
+        });
+        particles.push(oxygen);
+        particles.extend(hydrogens);
+    }
+    let mut scene = Scene::new("water", species, particles);
+    scene.periodic_box = periodic_box();
+    scene.topology = topology;
+    scene
+}
+
+/// A bead-spring polymer of 40 neutral Lennard-Jones beads joined by FENE springs, starting out
+/// as a jostled zigzag that crumples up into a coil.
+pub fn polymer_chain() -> Scene {
+    const BEADS: usize = 40;
+    const PER_ROW: usize = 10;
+    let mut species = SpeciesTable::default();
+    let bead = species.add(Species::new("bead", 1.0, 0.0, [0.3, 0.8, 0.5]));
+    let particles: Vec<_> = (0..BEADS)
+        .map(|i| {
+            let (row, column) = (i / PER_ROW, i % PER_ROW);
+            // run back and forth so neighbouring beads are always one apart
+            let column = if row % 2 == 0 {
+                column
+            } else {
+                PER_ROW - 1 - column
+            };
+            let position = cgmath::Point2::new(column as f64 - 4.5, row as f64 - 1.5);
+            let angle = (i * 7919 % 360) as f64 * std::f64::consts::PI / 180.0;
+            let velocity = cgmath::Vector2::new(angle.cos(), angle.sin()) * 0.5;
+            species.spawn(bead, position, velocity)
+        })
+        .collect();
+    let bonds = particles
+        .windows(2)
+        .map(|pair| Bond {
+            a: pair[0].id(),
+            b: pair[1].id(),
+            kind: BondKind::Fene {
+                stiffness: 30.0,
+                max_length: 1.5,
+            },
+        })
+        .collect();
+    let mut scene = Scene::new("polymer chain", species, particles);
+    scene.topology = Topology::new(bonds, vec![]);
+    scene
+}
+
+/// Particles per side of the cubic lattice the 3D scenes start from.
+const CUBE_SIDE: usize = 6;
+
+/// Directions spread evenly over the sphere, along a spiral a golden angle apart.
+fn spiral_direction(i: usize, count: usize) -> cgmath::Vector3<f64> {
+    let z = 1.0 - 2.0 * (i as f64 + 0.5) / count as f64;
+    let (sin, cos) = (i as f64 * GOLDEN_ANGLE).sin_cos();
+    let across = (1.0 - z * z).sqrt();
+    cgmath::vec3(across * cos, across * sin, z)
+}
+
+/// A 6 by 6 by 6 cubic lattice centred on the origin, the 3D version of `lattice`, with
+/// velocities of `speed` in every direction adding up to zero momentum. `species_at` picks
+/// the species at each corner.
+fn cube_lattice(
+    species: &SpeciesTable,
+    species_at: impl Fn(usize, usize, usize) -> SpeciesId,
+    speed: f64,
+) -> Vec<Particle<cgmath::Vector3<f64>>> {
+    let count = CUBE_SIDE.pow(3);
+    // shuffled, so neighbours don't move alike
+    let velocity = |i: usize| spiral_direction(i * 7919 % count, count) * speed;
+    let mean = (0..count).map(velocity).sum::<cgmath::Vector3<f64>>() / count as f64;
+    let offset = CUBE_SIDE as f64 * LATTICE_SPACING / 2.0;
+    (0..count)
+        .map(|i| {
+            let (x, y, z) = (
+                i % CUBE_SIDE,
+                i / CUBE_SIDE % CUBE_SIDE,
+                i / CUBE_SIDE.pow(2),
+            );
+            let position = cgmath::Point3::new(x as f64, y as f64, z as f64) * LATTICE_SPACING
+                + cgmath::vec3(1.0, 1.0, 1.0) * (LATTICE_SPACING / 2.0 - offset);
+            species.spawn(species_at(x, y, z), position, velocity(i) - mean)
+        })
+        .collect()
+}
+
+fn periodic_cube() -> Option<PeriodicBox<cgmath::Vector3<f64>>> {
+    let side = CUBE_SIDE as f64 * LATTICE_SPACING;
+    Some(PeriodicBox::centered(cgmath::vec3(side, side, side)))
+}
+
+/// A cube of neutral Lennard-Jones particles in open space, cool enough to pull itself
+/// together into a round drop.
+pub fn droplet() -> Scene<cgmath::Vector3<f64>> {
+    let species = SpeciesTable::default();
+    let particles = cube_lattice(&species, |_, _, _| SpeciesId::NEUTRAL, 0.3);
+    Scene::new("droplet", species, particles)
+}
+
+/// Neutral Lennard-Jones particles filling a periodic cube.
+pub fn periodic_gas_3d() -> Scene<cgmath::Vector3<f64>> {
+    let species = SpeciesTable::default();
+    let particles = cube_lattice(&species, |_, _, _| SpeciesId::NEUTRAL, 0.8);
+    let mut scene = Scene::new("periodic gas", species, particles);
+    scene.periodic_box = periodic_cube();
+    scene
+}
+
+/// The cubic lattice at rest with alternating charges, the actual rock salt structure.
+pub fn rock_salt() -> Scene<cgmath::Vector3<f64>> {
+    let species = SpeciesTable::default();
+    let particles = cube_lattice(
+        &species,
+        |x, y, z| [SpeciesId::CATION, SpeciesId::ANION][(x + y + z) % 2],
+        0.0,
+    );
+    let mut scene = Scene::new("rock salt", species, particles);
+    scene.periodic_box = periodic_cube();
+    scene
+}
+
+/// The polymer chain again, starting as a helix it can coil out of in any direction.
+pub fn polymer_coil() -> Scene<cgmath::Vector3<f64>> {
+    const BEADS: usize = 60;
+    let mut species = SpeciesTable::default();
+    let bead = species.add(Species::new("bead", 1.0, 0.0, [0.3, 0.8, 0.5]));
+    // eight beads to a turn of radius 1.3, rising 1.5 a turn, so neighbours are one apart
+    let particles: Vec<_> = (0..BEADS)
+        .map(|i| {
+            let angle = i as f64 * std::f64::consts::PI / 4.0;
+            let position = cgmath::Point3::new(
+                1.3 * angle.cos(),
+                1.3 * angle.sin(),
+                (i as f64 - BEADS as f64 / 2.0) * 1.5 / 8.0,
+            );
+            let velocity = spiral_direction(i * 7919 % BEADS, BEADS) * 0.5;
+            species.spawn(bead, position, velocity)
+        })
+        .collect();
+    let bonds = particles
+        .windows(2)
+        .map(|pair| Bond {
+            a: pair[0].id(),
+            b: pair[1].id(),
+            kind: BondKind::Fene {
+                stiffness: 30.0,
+                max_length: 1.5,
+            },
+        })
+        .collect();
+    let mut scene = Scene::new("polymer coil", species, particles);
+    scene.topology = Topology::new(bonds, vec![]);
+    scene
+}
+
+/// Charges of both signs in a magnetic field along z, circling it as they drift along it, so
+/// their orbits stretch out into helices.
+pub fn cyclotron_helices() -> Scene<cgmath::Vector3<f64>> {
+    let species = SpeciesTable::default();
+    let particles = (0..6)
+        .map(|i| {
+            let id = [SpeciesId::CATION, SpeciesId::ANION][i % 2];
+            let position = cgmath::Point3::new((i as f64 - 2.5) * 5.0, 0.0, -10.0);
+            let velocity = cgmath::vec3(0.0, 0.5 + 0.25 * i as f64, 0.4);
+            species.spawn(id, position, velocity)
+        })
+        .collect();
+    let mut scene = Scene::new("cyclotron helices", species, particles);
+    scene.external_field.magnetic = cgmath::vec3(0.0, 0.0, 0.5);
+    scene
+}
+
+/// Planets going round a sun on circular orbits tilted every which way.
+pub fn inclined_orbits() -> Scene<cgmath::Vector3<f64>> {
+    let gravity = Gravity {
+        softening: 0.1,
+        ..Gravity::default()
+    };
+    let mut species = SpeciesTable::default();
+    let sun = species.add(Species::new("sun", 100.0, 0.0, [1.0, 0.8, 0.3]).with_radius(1.0));
+    let planet = species.add(Species::new("planet", 0.1, 0.0, [0.3, 0.6, 1.0]).with_radius(0.3));
+    let at_rest = cgmath::vec3(0.0, 0.0, 0.0);
+    let mut particles = vec![species.spawn(sun, cgmath::Point3::origin(), at_rest)];
+    let radii = [4.0, 6.5, 9.0, 12.5, 16.0];
+    for (i, &radius) in radii.iter().enumerate() {
+        // out along one spiral direction and moving at right angles to it
+        let out = spiral_direction(i, radii.len());
+        let along = out
+            .cross(spiral_direction((i + 2) % radii.len(), radii.len()))
+            .normalize();
+        let speed = orbital_speed(&gravity, 100.0, radius);
+        particles.push(species.spawn(
+            planet,
+            cgmath::Point3::from_vec(out * radius),
+            along * speed,
+        ));
+    }
+    let momentum: cgmath::Vector3<f64> = particles.iter().map(|p| p.velocity() * p.mass()).sum();
+    particles[0].kick(-momentum / 100.0);
+    let mut scene = Scene::new("inclined orbits", species, particles);
+    scene.gravity = Some(gravity);
+    scene
+}
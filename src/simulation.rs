@@ -0,0 +1,388 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    barostat::{self, Barostat},
+    collision::Collisions,
+    dimension::Vector,
+    integrator::{self, Integrator, Solvent},
+    particle::{Particle, ParticleStore},
+    potential::{ForceField, PairPotential},
+    reaction::{BondEvent, Reactions},
+    thermostat::{self, Thermostat},
+    wall::{self, Wall},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepControl {
+    Fixed,
+    /// Splits each step into substeps until the step-doubling error estimate and the
+    /// displacement caused by the largest acceleration both stay below `tolerance`.
+    Adaptive {
+        tolerance: f64,
+        max_substeps: u32,
+    },
+}
+
+impl Default for StepControl {
+    fn default() -> Self {
+        StepControl::Adaptive {
+            tolerance: 1e-4,
+            max_substeps: 256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepReport {
+    pub substeps: u32,
+}
+
+pub struct Simulation<V: Vector = cgmath::Vector2<f64>> {
+    pub particles: ParticleStore<V>,
+    pub force_field: ForceField<V>,
+    pub integrator: Integrator,
+    pub step_control: StepControl,
+    pub walls: Vec<Wall>,
+    pub thermostat: Option<Thermostat>,
+    /// Only acts when there is a periodic box.
+    pub barostat: Option<Barostat>,
+    /// Used by the stochastic integrators.
+    pub solvent: Solvent,
+    /// Hard-sphere collisions, resolved after every step on top of the soft potentials.
+    pub collisions: Option<Collisions>,
+    /// Forms and breaks bonds in `force_field.topology` after every step.
+    pub reactions: Option<Reactions>,
+    /// Bonds formed and broken since the last `take_bond_events`.
+    bond_events: Vec<BondEvent<V>>,
+    /// Seeded so runs can be reproduced exactly.
+    rng: StdRng,
+}
+
+impl<V: Vector> Simulation<V> {
+    pub fn new(particles: Vec<Particle<V>>, potentials: Vec<Box<dyn PairPotential<V>>>) -> Self {
+        Self {
+            particles: particles.into_iter().collect(),
+            force_field: ForceField::new(potentials),
+            integrator: Integrator::default(),
+            step_control: StepControl::Fixed,
+            walls: vec![],
+            thermostat: None,
+            barostat: None,
+            solvent: Solvent::default(),
+            collisions: None,
+            reactions: None,
+            bond_events: vec![],
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    pub fn step(&mut self, dt: f64) -> StepReport {
+        let previous_positions: Vec<_> = if self.walls.is_empty() {
+            vec![]
+        } else {
+            self.particles.positions().to_vec()
+        };
+        let report = match self.step_control {
+            StepControl::Fixed => {
+                self.integrator.step(
+                    &mut self.particles,
+                    &mut self.force_field,
+                    dt,
+                    &self.solvent,
+                    &mut self.rng,
+                );
+                StepReport { substeps: 1 }
+            }
+            StepControl::Adaptive {
+                tolerance,
+                max_substeps,
+            } => self.step_adaptive(dt, tolerance, max_substeps),
+        };
+        if let Some(collisions) = &self.collisions {
+            collisions.resolve(&mut self.particles, self.force_field.periodic_box.as_ref());
+        }
+        if !self.walls.is_empty() {
+            let absorbed = wall::collide(
+                &mut self.walls,
+                &mut self.particles,
+                &previous_positions,
+                &mut self.rng,
+            );
+            if absorbed > 0 {
+                self.force_field.topology.remove_missing(&self.particles);
+            }
+        }
+        if let Some(reactions) = &self.reactions {
+            // taken out for the step, so the rest of the force field can lend its neighbour list
+            let mut topology = std::mem::take(&mut self.force_field.topology);
+            let context = self
+                .force_field
+                .context_within(&self.particles, reactions.capture_distance);
+            self.bond_events
+                .extend(reactions.apply(&context, &mut topology));
+            self.force_field.topology = topology;
+        }
+        if let Some(thermostat) = &mut self.thermostat {
+            thermostat.apply(&mut self.particles, dt, &mut self.rng);
+        }
+        if let Some(pressure) = self.barostat.as_ref().and_then(|_| self.pressure()) {
+            if let (Some(barostat), Some(periodic_box)) =
+                (&mut self.barostat, &mut self.force_field.periodic_box)
+            {
+                barostat.apply(&mut self.particles, periodic_box, pressure, dt);
+            }
+        }
+        if let Some(periodic_box) = &self.force_field.periodic_box {
+            self.particles
+                .move_all(|_, position| periodic_box.wrap(position));
+        }
+        report
+    }
+
+    pub fn take_bond_events(&mut self) -> Vec<BondEvent<V>> {
+        std::mem::take(&mut self.bond_events)
+    }
+
+    fn step_adaptive(&mut self, dt: f64, tolerance: f64, max_substeps: u32) -> StepReport {
+        let min_h = dt / max_substeps as f64;
+        let mut remaining = dt;
+        let mut substeps = 0;
+        while remaining > min_h * 1e-6 {
+            let max_acceleration =
+                integrator::accelerations(&self.particles, &mut self.force_field)
+                    .iter()
+                    .map(|a| a.magnitude())
+                    .fold(0.0, f64::max);
+            let mut h = remaining;
+            if max_acceleration > 0.0 {
+                h = h.min((2.0 * tolerance / max_acceleration).sqrt());
+            }
+            h = h.max(min_h).min(remaining);
+            if self.integrator.is_stochastic() {
+                // step doubling can't tell noise from error, so only the acceleration limit
+                // applies
+                self.integrator.step(
+                    &mut self.particles,
+                    &mut self.force_field,
+                    h,
+                    &self.solvent,
+                    &mut self.rng,
+                );
+            } else {
+                h = self.step_doubled(h, min_h, tolerance);
+            }
+            remaining -= h;
+            substeps += 1;
+        }
+        StepReport { substeps }
+    }
+
+    /// Takes a step of at most `h`, halving it until a full step and two half steps agree to
+    /// within `tolerance`. Returns the step actually taken.
+    fn step_doubled(&mut self, mut h: f64, min_h: f64, tolerance: f64) -> f64 {
+        let (solvent, rng) = (&self.solvent, &mut self.rng);
+        loop {
+            let mut full = self.particles.clone();
+            self.integrator
+                .step(&mut full, &mut self.force_field, h, solvent, rng);
+            let mut halves = self.particles.clone();
+            for _ in 0..2 {
+                self.integrator
+                    .step(&mut halves, &mut self.force_field, h / 2.0, solvent, rng);
+            }
+            let error = full
+                .positions()
+                .iter()
+                .zip(halves.positions())
+                .map(|(&a, &b)| (a - b).magnitude())
+                .fold(0.0, f64::max);
+            if error <= tolerance || h <= min_h {
+                self.particles = halves;
+                return h;
+            }
+            h = (h / 2.0).max(min_h);
+        }
+    }
+
+    pub fn kinetic_energy(&self) -> f64 {
+        self.particles
+            .iter()
+            .map(|p| 0.5 * p.mass() * p.velocity().magnitude2())
+            .sum()
+    }
+
+    /// Restarts the random number generator, for reproducing a run.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn temperature(&self) -> f64 {
+        thermostat::temperature(&self.particles)
+    }
+
+    /// Virial pressure, which needs a periodic box to have a volume.
+    pub fn pressure(&self) -> Option<f64> {
+        let periodic_box = self.force_field.periodic_box.as_ref()?;
+        Some(barostat::virial_pressure(
+            self.kinetic_energy(),
+            self.force_field.virial(&self.particles),
+            periodic_box.volume(),
+            V::DIMENSION,
+        ))
+    }
+
+    pub fn potential_energy(&self) -> f64 {
+        self.force_field.potential_energy(&self.particles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boundary::PeriodicBox,
+        potential::{Coulomb, LennardJones},
+    };
+    use cgmath::InnerSpace;
+
+    fn pair(distance: f64, q1: f64, q2: f64) -> Simulation {
+        Simulation::new(
+            vec![
+                Particle::new(cgmath::point2(0.0, 0.0), cgmath::vec2(0.0, 0.0), 1.0, q1),
+                Particle::new(
+                    cgmath::point2(distance, 0.0),
+                    cgmath::vec2(0.0, 0.0),
+                    2.0,
+                    q2,
+                ),
+            ],
+            vec![
+                Box::new(Coulomb::default()),
+                Box::new(LennardJones::default()),
+            ],
+        )
+    }
+
+    #[test]
+    fn step_conserves_momentum() {
+        let mut simulation = pair(1.5, 1.0, -2.0);
+        simulation.step(0.01);
+        let momentum: cgmath::Vector2<f64> = simulation
+            .particles
+            .iter()
+            .map(|p| p.velocity() * p.mass())
+            .sum();
+        assert!(momentum.magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn opposite_charges_form_a_bound_pair() {
+        let mut simulation = pair(3.0, 1.0, -1.0);
+        let mut closest = f64::INFINITY;
+        let mut furthest = 0.0f64;
+        for _ in 0..20000 {
+            simulation.step(1.0 / 600.0);
+            let d = (simulation.particles.get(0).position()
+                - simulation.particles.get(1).position())
+            .magnitude();
+            closest = closest.min(d);
+            furthest = furthest.max(d);
+        }
+        assert!(closest > 0.8, "collapsed to {}", closest);
+        assert!(furthest < 3.05, "escaped to {}", furthest);
+    }
+
+    fn head_on(step_control: StepControl) -> (Simulation, f64) {
+        let mut simulation = Simulation::new(
+            vec![
+                Particle::new(cgmath::point2(-3.0, 0.0), cgmath::vec2(2.0, 0.0), 1.0, 1.0),
+                Particle::new(cgmath::point2(3.0, 0.0), cgmath::vec2(-2.0, 0.0), 1.0, -1.0),
+            ],
+            vec![Box::new(Coulomb {
+                softening: 0.01,
+                ..Coulomb::default()
+            })],
+        );
+        simulation.step_control = step_control;
+        let energy = simulation.kinetic_energy() + simulation.potential_energy();
+        (simulation, energy)
+    }
+
+    #[test]
+    fn adaptive_stepping_survives_close_approach() {
+        let (mut fixed, initial) = head_on(StepControl::Fixed);
+        let (mut adaptive, _) = head_on(StepControl::default());
+        let mut max_substeps = 0;
+        for _ in 0..180 {
+            fixed.step(1.0 / 60.0);
+            max_substeps = max_substeps.max(adaptive.step(1.0 / 60.0).substeps);
+        }
+        let fixed_error = (fixed.kinetic_energy() + fixed.potential_energy() - initial).abs();
+        let adaptive_error =
+            (adaptive.kinetic_energy() + adaptive.potential_energy() - initial).abs();
+        assert!(
+            adaptive_error < 2e-2 * initial.abs(),
+            "drifted by {}",
+            adaptive_error
+        );
+        assert!(adaptive_error * 1000.0 < fixed_error);
+        assert!(max_substeps > 1);
+    }
+
+    #[test]
+    fn adaptive_stepping_does_not_subdivide_when_calm() {
+        let mut simulation = pair(30.0, 1.0, 1.0);
+        simulation.step_control = StepControl::default();
+        assert_eq!(simulation.step(1.0 / 60.0), StepReport { substeps: 1 });
+    }
+
+    #[test]
+    fn periodic_box_wraps_and_interacts_across_edges() {
+        let mut simulation = Simulation::new(
+            vec![
+                Particle::new(cgmath::point2(-4.25, 0.0), cgmath::vec2(0.0, 0.0), 1.0, 0.0),
+                Particle::new(cgmath::point2(4.25, 0.0), cgmath::vec2(0.0, 0.0), 1.0, 0.0),
+                Particle::new(cgmath::point2(0.0, 4.95), cgmath::vec2(0.0, 1.0), 1.0, 0.0),
+            ],
+            vec![Box::new(LennardJones::default())],
+        );
+        simulation.force_field.periodic_box = Some(PeriodicBox::centered(cgmath::vec2(10.0, 10.0)));
+        simulation.step(0.2);
+        // the first two are 1.5 apart through the edge, so they get pulled outwards
+        assert!(simulation.particles.get(0).velocity().x < 0.0);
+        assert!(simulation.particles.get(1).velocity().x > 0.0);
+        assert!((simulation.particles.get(2).position().y - -4.85).abs() < 1e-9);
+    }
+
+    #[test]
+    fn three_dimensional_scenes_conserve_energy() {
+        use crate::scene::Catalog;
+        for scene in cgmath::Vector3::scenes(None) {
+            let mut simulation = Simulation::new(
+                scene.particles,
+                vec![
+                    Box::new(Coulomb::default()),
+                    Box::new(LennardJones::default()),
+                ],
+            );
+            simulation.force_field.periodic_box = scene.periodic_box;
+            simulation.force_field.topology = scene.topology;
+            simulation.force_field.external_field = scene.external_field;
+            simulation.force_field.gravity = scene.gravity;
+            simulation.integrator = Integrator::Boris;
+            simulation.step_control = StepControl::default();
+            let initial = simulation.kinetic_energy() + simulation.potential_energy();
+            for _ in 0..20 {
+                simulation.step(1.0 / 60.0);
+            }
+            let energy = simulation.kinetic_energy() + simulation.potential_energy();
+            assert!(
+                (energy - initial).abs() < 1e-2 * initial.abs().max(1.0),
+                "{} went from {} to {}",
+                scene.name,
+                initial,
+                energy
+            );
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{
+    dimension::Vector,
+    particle::{LennardJonesParameters, Particle, ParticleRef, ParticleStore},
+};
+
+/// How big particles are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayScale {
+    /// At their physical radius.
+    Physical,
+    /// This many pixels across however far the camera zooms, so nothing is too small to see.
+    ConstantPixels(f64),
+    /// Growing with the logarithm of the mass, so heavy particles stand out without dwarfing
+    /// light ones.
+    LogMass,
+}
+
+impl DisplayScale {
+    pub const ALL: [DisplayScale; 3] = [
+        DisplayScale::Physical,
+        DisplayScale::ConstantPixels(12.0),
+        DisplayScale::LogMass,
+    ];
+
+    /// Radius to draw `particle` with, given how much of the world one pixel covers.
+    pub fn radius<V: Vector>(&self, particle: ParticleRef<V>, pixel_size: f64) -> f64 {
+        match *self {
+            DisplayScale::Physical => particle.radius(),
+            DisplayScale::ConstantPixels(pixels) => pixels / 2.0 * pixel_size,
+            // a particle of mass 1 is half a unit across, as big as a default Lennard-Jones one
+            DisplayScale::LogMass => 0.5 * (1.0 + particle.mass()).log2(),
+        }
+    }
+}
+
+/// How many species a table can hold, the size of the palette the shaders index into.
+pub const MAX_SPECIES: usize = 16;
+
+/// Index of a species in its `SpeciesTable`, and of its color in the palette. Laid out as a
+/// bare `u32`, so a column of them goes to the shaders as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct SpeciesId(u32);
+
+unsafe impl bytemuck::Zeroable for SpeciesId {}
+unsafe impl bytemuck::Pod for SpeciesId {}
+
+impl SpeciesId {
+    /// The stock species every table starts with, which ad hoc particles are drawn as.
+    pub const NEUTRAL: SpeciesId = SpeciesId(0);
+    pub const CATION: SpeciesId = SpeciesId(1);
+    pub const ANION: SpeciesId = SpeciesId(2);
+
+    /// The stock species for a particle with `charge`.
+    pub fn by_charge(charge: f64) -> Self {
+        if charge > 0.0 {
+            SpeciesId::CATION
+        } else if charge < 0.0 {
+            SpeciesId::ANION
+        } else {
+            SpeciesId::NEUTRAL
+        }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Everything particles of one kind have in common.
+#[derive(Debug, Clone)]
+pub struct Species {
+    pub name: &'static str,
+    pub mass: f64,
+    pub charge: f64,
+    pub lennard_jones: LennardJonesParameters,
+    /// Contact radius for hard-sphere collisions, and the size drawn at physical scale.
+    pub radius: f64,
+    /// How many bonds it can form by reacting, 0 for inert species.
+    pub valence: u32,
+    /// Squared in the shaders, so colors mix closer to how light does.
+    pub color: [f32; 3],
+    /// Overrides the global display scale for this species.
+    pub display_scale: Option<DisplayScale>,
+}
+
+impl Species {
+    /// An inert species with the default Lennard-Jones parameters.
+    pub fn new(name: &'static str, mass: f64, charge: f64, color: [f32; 3]) -> Self {
+        Self {
+            name,
+            mass,
+            charge,
+            lennard_jones: LennardJonesParameters::default(),
+            radius: LennardJonesParameters::default().sigma / 2.0,
+            valence: 0,
+            color,
+            display_scale: None,
+        }
+    }
+
+    /// Sets the Lennard-Jones parameters, and the radius to half of sigma, where the energy
+    /// of a pair crosses zero. Follow with `with_contact_radius` for a different one.
+    pub fn with_lennard_jones(mut self, lennard_jones: LennardJonesParameters) -> Self {
+        self.lennard_jones = lennard_jones;
+        self.radius = lennard_jones.sigma / 2.0;
+        self
+    }
+
+    /// Sets the radius, with the Lennard-Jones sigma following it as the diameter.
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = radius;
+        self.lennard_jones.sigma = 2.0 * radius;
+        self
+    }
+
+    /// Sets the radius alone, for species that collide as harder or softer spheres than their
+    /// Lennard-Jones sigma suggests.
+    pub fn with_contact_radius(mut self, radius: f64) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_display_scale(mut self, display_scale: DisplayScale) -> Self {
+        self.display_scale = Some(display_scale);
+        self
+    }
+
+    pub fn with_valence(mut self, valence: u32) -> Self {
+        self.valence = valence;
+        self
+    }
+}
+
+/// The species a scene is made of. Particles copy the physical properties of their species
+/// when they are spawned, so the force loops never need to look them up.
+#[derive(Debug, Clone)]
+pub struct SpeciesTable {
+    species: Vec<Species>,
+}
+
+/// The color of every species, laid out as a std140 array of `vec4`s for the shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    colors: [[f32; 4]; MAX_SPECIES],
+}
+
+unsafe impl bytemuck::Zeroable for Palette {}
+unsafe impl bytemuck::Pod for Palette {}
+
+impl Default for SpeciesTable {
+    fn default() -> Self {
+        Self {
+            species: vec![
+                Species::new("neutral", 1.0, 0.0, [1.0, 1.0, 1.0]),
+                Species::new("cation", 1.0, 1.0, [0.961, 0.0, 0.302]),
+                Species::new("anion", 1.0, -1.0, [0.286, 0.322, 1.0]),
+            ],
+        }
+    }
+}
+
+impl SpeciesTable {
+    /// Panics if the table already holds `MAX_SPECIES`, as the palette has no room for more.
+    pub fn add(&mut self, species: Species) -> SpeciesId {
+        assert!(self.species.len() < MAX_SPECIES, "too many species");
+        self.species.push(species);
+        SpeciesId(self.species.len() as u32 - 1)
+    }
+
+    pub fn get(&self, id: SpeciesId) -> &Species {
+        &self.species[id.0 as usize]
+    }
+
+    pub fn spawn<V: Vector>(&self, id: SpeciesId, position: V::Point, velocity: V) -> Particle<V> {
+        Particle::of_species(id, self.get(id), position, velocity)
+    }
+
+    /// How many of `particles` there are of each species, like "36 oxygen, 72 hydrogen",
+    /// leaving out the species there are none of.
+    pub fn census<V: Vector>(&self, particles: &ParticleStore<V>) -> String {
+        let mut counts = vec![0; self.species.len()];
+        for species in particles.species() {
+            counts[species.0 as usize] += 1;
+        }
+        self.species
+            .iter()
+            .zip(counts)
+            .filter(|&(_, count)| count > 0)
+            .map(|(species, count)| format!("{} {}", count, species.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Radius to draw `particle` with, at its species' display scale or else at `global`.
+    pub fn display_radius<V: Vector>(
+        &self,
+        particle: ParticleRef<V>,
+        global: DisplayScale,
+        pixel_size: f64,
+    ) -> f64 {
+        self.get(particle.species())
+            .display_scale
+            .unwrap_or(global)
+            .radius(particle, pixel_size)
+    }
+
+    pub fn palette(&self) -> Palette {
+        let mut colors = [[0.0; 4]; MAX_SPECIES];
+        for (color, species) in colors.iter_mut().zip(&self.species) {
+            let [r, g, b] = species.color;
+            *color = [r, g, b, 1.0];
+        }
+        Palette { colors }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawned_particles_take_their_species_properties() {
+        let mut table = SpeciesTable::default();
+        let oxygen = table.add(
+            Species::new("oxygen", 4.0, -0.8, [1.0, 0.2, 0.2])
+                .with_lennard_jones(LennardJonesParameters {
+                    epsilon: 0.5,
+                    sigma: 1.2,
+                })
+                .with_valence(2),
+        );
+        assert_eq!(oxygen.index(), 3);
+        let particle = table.spawn(oxygen, cgmath::point2(1.0, 2.0), cgmath::vec2(0.0, 1.0));
+        assert_eq!(particle.species(), oxygen);
+        assert_eq!(particle.mass(), 4.0);
+        assert_eq!(particle.charge(), -0.8);
+        assert_eq!(particle.lennard_jones().sigma, 1.2);
+        assert_eq!(particle.valence(), 2);
+        assert_eq!(particle.radius(), 0.6);
+
+        // a contact radius of its own leaves sigma where it was
+        let hard = table.add(
+            Species::new("hard", 1.0, 0.0, [0.5, 0.5, 0.5])
+                .with_radius(0.5)
+                .with_contact_radius(0.8),
+        );
+        let particle = table.spawn(hard, cgmath::point2(0.0, 0.0), cgmath::vec2(0.0, 0.0));
+        assert_eq!(particle.radius(), 0.8);
+        assert_eq!(particle.lennard_jones().sigma, 1.0);
+        assert_eq!(table.palette().colors[3], [1.0, 0.2, 0.2, 1.0]);
+    }
+
+    #[test]
+    fn census_counts_each_species() {
+        let mut table = SpeciesTable::default();
+        let bead = table.add(Species::new("bead", 1.0, 0.0, [0.3, 0.8, 0.5]));
+        let origin = cgmath::point2(0.0, 0.0);
+        let at_rest = cgmath::vec2(0.0, 0.0);
+        let particles: ParticleStore = vec![
+            table.spawn(bead, origin, at_rest),
+            table.spawn(SpeciesId::ANION, origin, at_rest),
+            table.spawn(bead, origin, at_rest),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(table.census(&particles), "1 anion, 2 bead");
+    }
+
+    #[test]
+    fn display_radius_follows_the_species_then_the_global_scale() {
+        let mut table = SpeciesTable::default();
+        let heavy = table.add(Species::new("heavy", 15.0, 0.0, [1.0, 1.0, 1.0]));
+        let marker = table.add(
+            Species::new("marker", 15.0, 0.0, [1.0, 1.0, 1.0])
+                .with_display_scale(DisplayScale::ConstantPixels(10.0)),
+        );
+        let origin = cgmath::point2(0.0, 0.0);
+        let at_rest = cgmath::vec2(0.0, 0.0);
+        let particles: ParticleStore = vec![
+            table.spawn(heavy, origin, at_rest),
+            table.spawn(marker, origin, at_rest),
+        ]
+        .into_iter()
+        .collect();
+        let (heavy, marker) = (particles.get(0), particles.get(1));
+        let radius = |particle, global| table.display_radius(particle, global, 0.01);
+
+        // heavy particles are no bigger physically, and only a little bigger on a log scale
+        assert_eq!(radius(heavy, DisplayScale::Physical), 0.5);
+        assert_eq!(radius(heavy, DisplayScale::LogMass), 2.0);
+        assert_eq!(radius(heavy, DisplayScale::ConstantPixels(20.0)), 0.1);
+        for &global in &DisplayScale::ALL {
+            assert_eq!(radius(marker, global), 0.05);
+        }
+    }
+
+    #[test]
+    fn ad_hoc_particles_are_drawn_by_the_sign_of_their_charge() {
+        let particle = |charge| {
+            Particle::new(
+                cgmath::point2(0.0, 0.0),
+                cgmath::vec2(0.0, 0.0),
+                1.0,
+                charge,
+            )
+            .species()
+        };
+        assert_eq!(particle(0.0), SpeciesId::NEUTRAL);
+        assert_eq!(particle(0.5), SpeciesId::CATION);
+        assert_eq!(particle(-2.0), SpeciesId::ANION);
+    }
+}
@@ -0,0 +1,37 @@
+#version 440 core
+
+layout(location = 0) in vec3 species_color;
+layout(location = 1) in vec3 view_position;
+layout(location = 2) in vec3 view_center;
+layout(location = 3) in float radius;
+
+layout(location = 0) out vec4 out_color;
+
+layout(std140, set = 0, binding = 0) uniform transform {
+    mat4 view;
+    mat4 projection;
+};
+
+const vec3 LIGHT = vec3(0.37, 0.56, 0.74);
+
+void main() {
+    // casts the ray through this pixel at the sphere, straight ahead when orthographic
+    bool orthographic = projection[3][3] == 1.0;
+    vec3 origin = orthographic ? vec3(view_position.xy, 0) : vec3(0);
+    vec3 direction = orthographic ? vec3(0, 0, -1) : normalize(view_position);
+    vec3 offset = origin - view_center;
+    float b = dot(offset, direction);
+    float discriminant = b * b - dot(offset, offset) + radius * radius;
+    if (discriminant < 0) {
+        discard;
+    }
+    vec3 hit = origin + direction * (-b - sqrt(discriminant));
+    vec3 normal = (hit - view_center) / radius;
+
+    float diffuse = max(dot(normal, LIGHT), 0);
+    float specular = pow(max(dot(normal, normalize(LIGHT - direction)), 0), 40);
+    out_color = vec4(species_color * (0.25 + 0.75 * diffuse) + vec3(0.3 * specular), 1);
+
+    vec4 clip = projection * vec4(hit, 1);
+    gl_FragDepth = clip.z / clip.w;
+}
@@ -0,0 +1,42 @@
+#version 440 core
+
+layout(location = 0) in vec2 vert_position;
+layout(location = 1) in vec3 particle_position;
+layout(location = 2) in float radius;
+layout(location = 3) in uint species;
+
+layout(location = 0) out vec3 species_color;
+layout(location = 1) out vec3 view_position;
+layout(location = 2) out vec3 view_center;
+layout(location = 3) out float out_radius;
+
+layout(std140, set = 0, binding = 0) uniform transform {
+    mat4 view;
+    mat4 projection;
+};
+
+layout(std140, set = 0, binding = 1) uniform palette {
+    vec4 colors[16];
+};
+
+void main() {
+    vec3 center = (view * vec4(particle_position, 1)).xyz;
+    vec3 right = vec3(1, 0, 0);
+    vec3 up = vec3(0, 1, 0);
+    float size = radius;
+    bool orthographic = projection[3][3] == 1.0;
+    if (!orthographic) {
+        // a square facing the eye, just big enough to hold the cone of rays touching the sphere
+        float distance2 = dot(center, center);
+        vec3 facing = -center / sqrt(distance2);
+        right = normalize(cross(abs(facing.y) < 0.99 ? vec3(0, 1, 0) : vec3(1, 0, 0), facing));
+        up = cross(facing, right);
+        size = radius * sqrt(distance2 / max(distance2 - radius * radius, 1e-6));
+    }
+    view_position = center + (vert_position.x * right + vert_position.y * up) * size;
+    view_center = center;
+    out_radius = radius;
+    gl_Position = projection * vec4(view_position, 1);
+    species_color = colors[species].rgb;
+    species_color = species_color * species_color;
+}
@@ -0,0 +1,189 @@
+use rand::Rng;
+use rand_distr::{ChiSquared, StandardNormal};
+
+use crate::{dimension::Vector, particle::ParticleStore};
+
+/// Instantaneous temperature in units where Boltzmann's constant is 1, from the kinetic energy
+/// of the two or three degrees of freedom of every particle.
+pub fn temperature<V: Vector>(particles: &ParticleStore<V>) -> f64 {
+    if particles.is_empty() {
+        return 0.0;
+    }
+    let kinetic: f64 = particles
+        .iter()
+        .map(|p| 0.5 * p.mass() * p.velocity().magnitude2())
+        .sum();
+    2.0 * kinetic / (V::DIMENSION * particles.len()) as f64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThermostatKind {
+    /// Relaxes the temperature exponentially towards the target. Quick and stable, but the
+    /// fluctuations it leaves are too small to be canonical.
+    Berendsen,
+    /// Bussi-Donadio-Parrinello stochastic velocity rescaling, Berendsen plus just enough noise
+    /// to sample the canonical ensemble.
+    VelocityRescaling,
+    /// Deterministic friction that is itself driven by the temperature error, so the
+    /// temperature oscillates around the target with a period of about the coupling time.
+    NoseHoover,
+}
+
+impl ThermostatKind {
+    pub const ALL: [ThermostatKind; 3] = [
+        ThermostatKind::Berendsen,
+        ThermostatKind::VelocityRescaling,
+        ThermostatKind::NoseHoover,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThermostatKind::Berendsen => "berendsen",
+            ThermostatKind::VelocityRescaling => "velocity rescaling",
+            ThermostatKind::NoseHoover => "nose-hoover",
+        }
+    }
+}
+
+/// Drives the velocities towards a target temperature after every step.
+#[derive(Debug, Clone)]
+pub struct Thermostat {
+    pub kind: ThermostatKind,
+    /// Can be changed at any time, the thermostat follows within a few coupling times.
+    pub target: f64,
+    pub coupling_time: f64,
+    /// The Nosé-Hoover friction coefficient.
+    friction: f64,
+}
+
+impl Thermostat {
+    pub fn new(kind: ThermostatKind, target: f64, coupling_time: f64) -> Self {
+        Self {
+            kind,
+            target,
+            coupling_time,
+            friction: 0.0,
+        }
+    }
+
+    pub fn apply<V: Vector>(
+        &mut self,
+        particles: &mut ParticleStore<V>,
+        dt: f64,
+        rng: &mut impl Rng,
+    ) {
+        let current = temperature(particles);
+        if current == 0.0 {
+            return;
+        }
+        let ratio = self.target / current;
+        let scale = match self.kind {
+            ThermostatKind::Berendsen => (1.0 + dt / self.coupling_time * (ratio - 1.0))
+                .max(0.0)
+                .sqrt(),
+            ThermostatKind::VelocityRescaling => {
+                let dof = (V::DIMENSION * particles.len()) as f64;
+                let decay = (-dt / self.coupling_time).exp();
+                let factor = ratio / dof * (1.0 - decay);
+                let r: f64 = rng.sample(StandardNormal);
+                let rest = if dof > 1.0 {
+                    rng.sample(ChiSquared::new(dof - 1.0).unwrap())
+                } else {
+                    0.0
+                };
+                (decay + factor * (r * r + rest) + 2.0 * r * (decay * factor).sqrt())
+                    .max(0.0)
+                    .sqrt()
+            }
+            ThermostatKind::NoseHoover => {
+                self.friction +=
+                    dt / (self.coupling_time * self.coupling_time) * (1.0 / ratio - 1.0);
+                (-self.friction * dt).exp()
+            }
+        };
+        for velocity in particles.velocities_mut() {
+            *velocity *= scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use rand::SeedableRng;
+
+    /// A hot ideal gas, so the temperature only changes through the thermostat.
+    fn gas() -> ParticleStore {
+        (0..100)
+            .map(|i| {
+                let angle = i as f64 * 2.4;
+                let speed = 1.0 + (i % 7) as f64 * 0.1;
+                Particle::new(
+                    cgmath::point2(0.0, 0.0),
+                    cgmath::vec2(angle.cos(), angle.sin()) * speed,
+                    1.0 + (i % 3) as f64,
+                    0.0,
+                )
+            })
+            .collect()
+    }
+
+    /// Runs a thermostat for `steps`, returning the temperature averaged over the second half.
+    fn run(kind: ThermostatKind, steps: usize) -> f64 {
+        let mut particles = gas();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let mut thermostat = Thermostat::new(kind, 0.5, 0.5);
+        let mut sum = 0.0;
+        for step in 0..steps {
+            thermostat.apply(&mut particles, 0.01, &mut rng);
+            if step >= steps / 2 {
+                sum += temperature(&particles);
+            }
+        }
+        sum / (steps - steps / 2) as f64
+    }
+
+    #[test]
+    fn temperature_of_known_velocities() {
+        let particles: ParticleStore = vec![
+            Particle::new(cgmath::point2(0.0, 0.0), cgmath::vec2(1.0, 0.0), 2.0, 0.0),
+            Particle::new(cgmath::point2(1.0, 0.0), cgmath::vec2(0.0, -2.0), 1.0, 0.0),
+        ]
+        .into_iter()
+        .collect();
+        assert!((temperature(&particles) - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn berendsen_relaxes_to_the_target() {
+        assert!((run(ThermostatKind::Berendsen, 2000) - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn velocity_rescaling_averages_to_the_target() {
+        let average = run(ThermostatKind::VelocityRescaling, 20000);
+        assert!((average - 0.5).abs() < 0.02, "{}", average);
+    }
+
+    #[test]
+    fn nose_hoover_averages_to_the_target() {
+        let average = run(ThermostatKind::NoseHoover, 20000);
+        assert!((average - 0.5).abs() < 0.02, "{}", average);
+    }
+
+    #[test]
+    fn follows_a_new_setpoint() {
+        let mut particles = gas();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let mut thermostat = Thermostat::new(ThermostatKind::Berendsen, 0.5, 0.1);
+        for _ in 0..500 {
+            thermostat.apply(&mut particles, 0.01, &mut rng);
+        }
+        thermostat.target = 2.0;
+        for _ in 0..500 {
+            thermostat.apply(&mut particles, 0.01, &mut rng);
+        }
+        assert!((temperature(&particles) - 2.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::{
+    boundary::{self, PeriodicBox},
+    dimension::Vector,
+    particle::{ParticleId, ParticleStore},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BondKind {
+    /// `stiffness / 2 * (r - rest_length)^2`
+    Harmonic { stiffness: f64, rest_length: f64 },
+    /// Finitely extensible nonlinear elastic spring,
+    /// `-stiffness / 2 * max_length^2 * ln(1 - (r / max_length)^2)`. Stretching it to
+    /// `max_length` would take infinite energy, so the force is capped just short of it.
+    Fene { stiffness: f64, max_length: f64 },
+}
+
+impl BondKind {
+    /// Radial force at length `r`, positive when pushing the ends apart, and the energy.
+    fn evaluate(&self, r: f64) -> (f64, f64) {
+        match *self {
+            BondKind::Harmonic {
+                stiffness,
+                rest_length,
+            } => (
+                -stiffness * (r - rest_length),
+                0.5 * stiffness * (r - rest_length).powi(2),
+            ),
+            BondKind::Fene {
+                stiffness,
+                max_length,
+            } => {
+                let x = (r / max_length).min(0.99);
+                let r = x * max_length;
+                (
+                    -stiffness * r / (1.0 - x * x),
+                    -0.5 * stiffness * max_length * max_length * (1.0 - x * x).ln(),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bond {
+    pub a: ParticleId,
+    pub b: ParticleId,
+    pub kind: BondKind,
+}
+
+/// Harmonic bending term `stiffness / 2 * (angle - rest_angle)^2` for the angle at `vertex`
+/// between the bonds to `a` and `b`, in radians between 0 and pi.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Angle {
+    pub a: ParticleId,
+    pub vertex: ParticleId,
+    pub b: ParticleId,
+    pub stiffness: f64,
+    pub rest_angle: f64,
+}
+
+/// The bonded interactions holding molecules together, which act on top of the pair
+/// potentials. Terms refer to particles by id, and terms involving a particle that no longer
+/// exists are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    pub bonds: Vec<Bond>,
+    pub angles: Vec<Angle>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Sums {
+    energy: f64,
+    virial: f64,
+}
+
+impl Topology {
+    pub fn new(bonds: Vec<Bond>, angles: Vec<Angle>) -> Self {
+        Self { bonds, angles }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bonds.is_empty() && self.angles.is_empty()
+    }
+
+    /// Drops every term involving a particle that isn't in `particles` any more.
+    pub fn remove_missing<V: Vector>(&mut self, particles: &ParticleStore<V>) {
+        let present = |id: &ParticleId| particles.index_of(*id).is_some();
+        self.bonds
+            .retain(|bond| present(&bond.a) && present(&bond.b));
+        self.angles
+            .retain(|angle| [angle.a, angle.vertex, angle.b].iter().all(present));
+    }
+
+    /// The indices into `particles` of the ends of every bond, in order, or `None` for bonds
+    /// with an end that no longer exists.
+    pub fn bond_ends<V: Vector>(
+        &self,
+        particles: &ParticleStore<V>,
+    ) -> Vec<Option<(usize, usize)>> {
+        self.bonds
+            .iter()
+            .map(|bond| Some((particles.index_of(bond.a)?, particles.index_of(bond.b)?)))
+            .collect()
+    }
+
+    /// Pairs of indices into `particles` of every bond whose ends both exist, for drawing.
+    pub fn bonded_pairs<V: Vector>(&self, particles: &ParticleStore<V>) -> Vec<(usize, usize)> {
+        self.bond_ends(particles).into_iter().flatten().collect()
+    }
+
+    pub fn accumulate<V: Vector>(
+        &self,
+        particles: &ParticleStore<V>,
+        periodic_box: Option<&PeriodicBox<V>>,
+        forces: &mut [V],
+    ) {
+        self.evaluate(particles, periodic_box, Some(forces));
+    }
+
+    pub fn energy<V: Vector>(
+        &self,
+        particles: &ParticleStore<V>,
+        periodic_box: Option<&PeriodicBox<V>>,
+    ) -> f64 {
+        self.evaluate(particles, periodic_box, None).energy
+    }
+
+    /// Sum of `r . F` over every term, the bonds' contribution to the pressure.
+    pub fn virial<V: Vector>(
+        &self,
+        particles: &ParticleStore<V>,
+        periodic_box: Option<&PeriodicBox<V>>,
+    ) -> f64 {
+        self.evaluate(particles, periodic_box, None).virial
+    }
+
+    fn evaluate<V: Vector>(
+        &self,
+        particles: &ParticleStore<V>,
+        periodic_box: Option<&PeriodicBox<V>>,
+        mut forces: Option<&mut [V]>,
+    ) -> Sums {
+        let mut sums = Sums::default();
+        if self.is_empty() {
+            return sums;
+        }
+        let positions = particles.positions();
+        let displacement =
+            |i: usize, j: usize| boundary::displacement(periodic_box, positions[i], positions[j]);
+
+        for bond in &self.bonds {
+            let (i, j) = match (particles.index_of(bond.a), particles.index_of(bond.b)) {
+                (Some(i), Some(j)) => (i, j),
+                _ => continue,
+            };
+            let r = displacement(i, j);
+            let d = r.magnitude();
+            let (force, energy) = bond.kind.evaluate(d);
+            sums.energy += energy;
+            sums.virial += force * d;
+            if let (Some(forces), true) = (&mut forces, d > 0.0) {
+                let force = r * (force / d);
+                forces[i] += force;
+                forces[j] -= force;
+            }
+        }
+
+        for angle in &self.angles {
+            let (a, vertex, b) = match (
+                particles.index_of(angle.a),
+                particles.index_of(angle.vertex),
+                particles.index_of(angle.b),
+            ) {
+                (Some(a), Some(vertex), Some(b)) => (a, vertex, b),
+                _ => continue,
+            };
+            let u = displacement(a, vertex);
+            let w = displacement(b, vertex);
+            let (lu, lw) = (u.magnitude(), w.magnitude());
+            if lu == 0.0 || lw == 0.0 {
+                continue;
+            }
+            // the parts of each bond at right angles to the other, in the plane of the angle
+            let u_across = u - w * (u.dot(w) / (lw * lw));
+            let w_across = w - u * (u.dot(w) / (lu * lu));
+            let theta = (w_across.magnitude() * lu).atan2(u.dot(w));
+            let error = theta - angle.rest_angle;
+            sums.energy += 0.5 * angle.stiffness * error * error;
+            // moving either end sideways towards the other is the only way to close the angle,
+            // so the forces are perpendicular to the bonds, and have no direction at all once
+            // the bonds line up
+            let (lu_across, lw_across) = (u_across.magnitude(), w_across.magnitude());
+            if lu_across == 0.0 || lw_across == 0.0 {
+                continue;
+            }
+            let force_a = w_across * (angle.stiffness * error / (lu * lw_across));
+            let force_b = u_across * (angle.stiffness * error / (lw * lu_across));
+            // and since angles don't change when the box is scaled, they add nothing to the virial
+            if let Some(forces) = &mut forces {
+                forces[a] += force_a;
+                forces[b] += force_b;
+                forces[vertex] -= force_a + force_b;
+            }
+        }
+        sums
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use cgmath::InnerSpace;
+
+    fn particle(x: f64, y: f64) -> Particle {
+        Particle::new(cgmath::point2(x, y), cgmath::vec2(0.0, 0.0), 1.0, 0.0)
+    }
+
+    fn bent_chain() -> (ParticleStore, Topology) {
+        let particles: ParticleStore = vec![
+            particle(0.0, 0.0),
+            particle(1.3, 0.2),
+            particle(1.9, 1.1),
+            particle(1.2, 2.0),
+        ]
+        .into_iter()
+        .collect();
+        let id = |i: usize| particles.ids()[i];
+        let topology = Topology {
+            bonds: vec![
+                Bond {
+                    a: id(0),
+                    b: id(1),
+                    kind: BondKind::Harmonic {
+                        stiffness: 50.0,
+                        rest_length: 1.0,
+                    },
+                },
+                Bond {
+                    a: id(1),
+                    b: id(2),
+                    kind: BondKind::Fene {
+                        stiffness: 30.0,
+                        max_length: 1.5,
+                    },
+                },
+            ],
+            angles: vec![
+                Angle {
+                    a: id(0),
+                    vertex: id(1),
+                    b: id(2),
+                    stiffness: 10.0,
+                    rest_angle: 1.9,
+                },
+                Angle {
+                    a: id(3),
+                    vertex: id(2),
+                    b: id(1),
+                    stiffness: 5.0,
+                    rest_angle: 2.5,
+                },
+            ],
+        };
+        (particles, topology)
+    }
+
+    fn forces(particles: &ParticleStore, topology: &Topology) -> Vec<cgmath::Vector2<f64>> {
+        let mut forces = vec![cgmath::vec2(0.0, 0.0); particles.len()];
+        topology.accumulate(particles, None, &mut forces);
+        forces
+    }
+
+    #[test]
+    fn forces_are_the_energy_gradient() {
+        let (mut particles, topology) = bent_chain();
+        let analytic = forces(&particles, &topology);
+        let h = 1e-6;
+        for i in 0..particles.len() {
+            for &direction in &[cgmath::vec2(1.0, 0.0), cgmath::vec2(0.0, 1.0)] {
+                particles.get_mut(i).drift(direction * h);
+                let plus = topology.energy(&particles, None);
+                particles.get_mut(i).drift(direction * (-2.0 * h));
+                let minus = topology.energy(&particles, None);
+                particles.get_mut(i).drift(direction * h);
+                let numeric = -(plus - minus) / (2.0 * h);
+                let analytic = analytic[i].dot(direction);
+                assert!(
+                    (numeric - analytic).abs() < 1e-5,
+                    "{} {}",
+                    numeric,
+                    analytic
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn forces_have_no_net_force_or_torque() {
+        let (particles, topology) = bent_chain();
+        let forces = forces(&particles, &topology);
+        let net: cgmath::Vector2<f64> = forces.iter().sum();
+        let torque: f64 = particles
+            .iter()
+            .zip(&forces)
+            .map(|(p, f)| p.position().x * f.y - p.position().y * f.x)
+            .sum();
+        assert!(net.magnitude() < 1e-9);
+        assert!(torque.abs() < 1e-9);
+    }
+
+    #[test]
+    fn terms_follow_their_particles_when_others_are_removed() {
+        let (mut particles, mut topology) = bent_chain();
+        let before = forces(&particles, &topology);
+        let removed = particles.remove(particles.ids()[0]).unwrap();
+        topology.remove_missing(&particles);
+        assert_eq!(topology.bonds.len(), 1);
+        assert_eq!(topology.angles.len(), 1);
+        assert!(topology
+            .bonds
+            .iter()
+            .all(|bond| bond.a != removed.id() && bond.b != removed.id()));
+
+        // the last particle is only held by the second angle, which survived and now acts on
+        // it where it was moved to fill the gap
+        let after = forces(&particles, &topology);
+        assert_eq!(after.len(), 3);
+        assert!((before[3] - after[0]).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn bonds_use_the_nearest_image() {
+        let periodic_box = PeriodicBox::centered(cgmath::vec2(10.0, 10.0));
+        let particles: ParticleStore = vec![particle(4.6, 0.0), particle(-4.6, 0.0)]
+            .into_iter()
+            .collect();
+        let topology = Topology::new(
+            vec![Bond {
+                a: particles.ids()[0],
+                b: particles.ids()[1],
+                kind: BondKind::Harmonic {
+                    stiffness: 1.0,
+                    rest_length: 1.0,
+                },
+            }],
+            vec![],
+        );
+        // 0.8 apart across the edge, so the bond pushes them further across it
+        let mut forces = vec![cgmath::vec2(0.0, 0.0); 2];
+        topology.accumulate(&particles, Some(&periodic_box), &mut forces);
+        assert!((forces[0] - cgmath::vec2(-0.2, 0.0)).magnitude() < 1e-12);
+        assert!((topology.energy(&particles, Some(&periodic_box)) - 0.02).abs() < 1e-12);
+    }
+
+    #[test]
+    fn water_molecules_keep_their_shape() {
+        let scene = crate::scene::water();
+        let mut simulation = crate::simulation::Simulation::new(
+            scene.particles,
+            vec![
+                Box::new(crate::potential::Coulomb::default()),
+                Box::new(crate::potential::LennardJones::default()),
+            ],
+        );
+        simulation.force_field.periodic_box = scene.periodic_box;
+        simulation.force_field.topology = scene.topology;
+        for _ in 0..500 {
+            simulation.step(0.005);
+        }
+        let periodic_box = simulation.force_field.periodic_box;
+        let particles = &simulation.particles;
+        let topology = &simulation.force_field.topology;
+        for (i, j) in topology.bonded_pairs(particles) {
+            let length = boundary::displacement(
+                periodic_box.as_ref(),
+                particles.get(i).position(),
+                particles.get(j).position(),
+            )
+            .magnitude();
+            assert!((length - 1.0).abs() < 0.15, "{}", length);
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use cgmath::{EuclideanSpace, InnerSpace};
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+use crate::{dimension::Vector, particle::ParticleStore};
+
+/// How many wall hits a single particle can go through in one step, which only matters for
+/// particles bouncing around in a corner.
+const MAX_HITS_PER_STEP: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WallKind {
+    /// Elastic reflection.
+    Reflective,
+    /// Removes every particle that hits it.
+    Absorbing,
+    /// Re-emits particles with a velocity drawn from the flux of a gas at `temperature`.
+    Thermal { temperature: f64 },
+}
+
+/// A line segment that particle centers can't cross. Walls are two sided, so a closed
+/// container is just a loop of them. In 3D they stand upright on the segment, endlessly tall
+/// along z.
+#[derive(Debug, Clone)]
+pub struct Wall {
+    start: cgmath::Point2<f64>,
+    end: cgmath::Point2<f64>,
+    pub kind: WallKind,
+    /// Total momentum particles have given to the wall.
+    momentum_transferred: cgmath::Vector2<f64>,
+}
+
+struct Hit<V: Vector> {
+    wall: usize,
+    fraction: f64,
+    point: V::Point,
+}
+
+/// Where `p` is seen from above, in the plane the walls are drawn in.
+fn plan<V: Vector>(p: V::Point) -> cgmath::Point2<f64> {
+    cgmath::Point2::from_vec(p.to_vec().xy())
+}
+
+impl Wall {
+    pub fn new(start: cgmath::Point2<f64>, end: cgmath::Point2<f64>, kind: WallKind) -> Self {
+        Self {
+            start,
+            end,
+            kind,
+            momentum_transferred: cgmath::vec2(0.0, 0.0),
+        }
+    }
+
+    /// The four sides of an axis-aligned box, bottom, right, top, left.
+    pub fn rectangle(
+        min: cgmath::Point2<f64>,
+        max: cgmath::Point2<f64>,
+        kind: WallKind,
+    ) -> Vec<Wall> {
+        let corners = [
+            min,
+            cgmath::point2(max.x, min.y),
+            max,
+            cgmath::point2(min.x, max.y),
+        ];
+        corners
+            .iter()
+            .zip(corners.iter().cycle().skip(1))
+            .map(|(&a, &b)| Wall::new(a, b, kind))
+            .collect()
+    }
+
+    pub fn start(&self) -> cgmath::Point2<f64> {
+        self.start
+    }
+
+    pub fn end(&self) -> cgmath::Point2<f64> {
+        self.end
+    }
+
+    pub fn length(&self) -> f64 {
+        (self.end - self.start).magnitude()
+    }
+
+    /// Unit normal, pointing to the left when looking from `start` to `end`.
+    pub fn normal(&self) -> cgmath::Vector2<f64> {
+        let d = (self.end - self.start).normalize();
+        cgmath::vec2(-d.y, d.x)
+    }
+
+    pub fn momentum_transferred(&self) -> cgmath::Vector2<f64> {
+        self.momentum_transferred
+    }
+
+    pub fn reset_momentum_transferred(&mut self) {
+        self.momentum_transferred = cgmath::vec2(0.0, 0.0);
+    }
+
+    /// Where along `from -> to` the path crosses the wall, as a fraction of the path. Paths
+    /// starting exactly on the wall move away from it, so they don't count.
+    fn crossing(&self, from: cgmath::Point2<f64>, to: cgmath::Point2<f64>) -> Option<f64> {
+        let normal = self.normal();
+        let d0 = (from - self.start).dot(normal);
+        let d1 = (to - self.start).dot(normal);
+        if d0 == 0.0 || ((d0 > 0.0) == (d1 > 0.0) && d1 != 0.0) {
+            return None;
+        }
+        let fraction = d0 / (d0 - d1);
+        let point = from + (to - from) * fraction;
+        let along =
+            (point - self.start).dot(self.end - self.start) / (self.end - self.start).magnitude2();
+        if (0.0..=1.0).contains(&along) {
+            Some(fraction)
+        } else {
+            None
+        }
+    }
+}
+
+fn first_hit<V: Vector>(
+    walls: &[Wall],
+    from: V::Point,
+    to: V::Point,
+    skip: Option<usize>,
+) -> Option<Hit<V>> {
+    walls
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| Some(i) != skip)
+        .filter_map(|(i, wall)| {
+            wall.crossing(plan::<V>(from), plan::<V>(to))
+                .map(|fraction| (i, fraction))
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(wall, fraction)| Hit {
+            wall,
+            fraction,
+            point: from + (to - from) * fraction,
+        })
+}
+
+/// Resolves every wall the particles ran through on their way from `previous_positions` to
+/// where they are now, removing absorbed particles. Returns how many were absorbed.
+pub fn collide<V: Vector>(
+    walls: &mut [Wall],
+    particles: &mut ParticleStore<V>,
+    previous_positions: &[V::Point],
+    rng: &mut impl Rng,
+) -> usize {
+    let mut absorbed = vec![false; particles.len()];
+    for (i, absorbed) in absorbed.iter_mut().enumerate() {
+        let mut particle = particles.get_mut(i);
+        let mut from = previous_positions[i];
+        let mut skip = None;
+        for _ in 0..MAX_HITS_PER_STEP {
+            let hit = match first_hit::<V>(walls, from, particle.position(), skip) {
+                Some(hit) => hit,
+                None => break,
+            };
+            let wall = &mut walls[hit.wall];
+            let normal = wall.normal();
+            // the side of the wall the particle came from
+            let back = if (plan::<V>(from) - wall.start).dot(normal) > 0.0 {
+                normal
+            } else {
+                -normal
+            };
+            let tangent = V::from_xy(cgmath::vec2(back.y, -back.x));
+            let (normal, back) = (V::from_xy(normal), V::from_xy(back));
+            let velocity = particle.velocity();
+            match wall.kind {
+                WallKind::Reflective => {
+                    let past: V = particle.position() - hit.point;
+                    let beyond = past.dot(normal);
+                    particle.set_position(particle.position() - normal * (2.0 * beyond));
+                    particle.set_velocity(velocity - normal * (2.0 * velocity.dot(normal)));
+                }
+                WallKind::Absorbing => {
+                    *absorbed = true;
+                    wall.momentum_transferred += (velocity * particle.mass()).xy();
+                    break;
+                }
+                WallKind::Thermal { temperature } => {
+                    // the normal speed of particles crossing a surface is Rayleigh distributed
+                    let scale = (temperature / particle.mass()).sqrt();
+                    let u: f64 = rng.gen();
+                    let normal_speed = scale * (-2.0 * (1.0 - u).ln()).sqrt();
+                    let tangent_speed = scale * rng.sample::<f64, _>(StandardNormal);
+                    // spend what is left of the step moving away from the wall
+                    let remaining = (particle.position() - from).magnitude() * (1.0 - hit.fraction)
+                        / velocity.magnitude().max(f64::MIN_POSITIVE);
+                    let mut emitted = back * normal_speed + tangent * tangent_speed;
+                    // and along the wall's height in 3D
+                    for i in 2..V::DIMENSION {
+                        emitted[i] = scale * rng.sample::<f64, _>(StandardNormal);
+                    }
+                    particle.set_position(hit.point + emitted * remaining);
+                    particle.set_velocity(emitted);
+                }
+            }
+            wall.momentum_transferred += ((velocity - particle.velocity()) * particle.mass()).xy();
+            from = hit.point;
+            skip = Some(hit.wall);
+        }
+    }
+    particles.retain(|p| !absorbed[p.index()]);
+    absorbed.iter().filter(|&&a| a).count()
+}
+
+/// Average force per unit length pushing the walls outwards over the last `duration`, going by
+/// the momentum they have been given since it was last reset. Walls are counted with the
+/// normal facing away from `inside`.
+pub fn pressure(walls: &[Wall], inside: cgmath::Point2<f64>, duration: f64) -> f64 {
+    let length: f64 = walls.iter().map(Wall::length).sum();
+    if length == 0.0 || duration <= 0.0 {
+        return 0.0;
+    }
+    let impulse: f64 = walls
+        .iter()
+        .map(|wall| {
+            let normal = wall.normal();
+            let outwards = if (inside - wall.start).dot(normal) > 0.0 {
+                -normal
+            } else {
+                normal
+            };
+            wall.momentum_transferred().dot(outwards)
+        })
+        .sum();
+    impulse / (length * duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use rand::SeedableRng;
+
+    fn rng() -> rand::rngs::StdRng {
+        rand::rngs::StdRng::seed_from_u64(7)
+    }
+
+    fn moving(from: cgmath::Point2<f64>, dt: f64, velocity: cgmath::Vector2<f64>) -> Particle {
+        Particle::new(from + velocity * dt, velocity, 2.0, 0.0)
+    }
+
+    #[test]
+    fn reflects_elastically() {
+        let mut walls = vec![Wall::new(
+            cgmath::point2(0.0, -5.0),
+            cgmath::point2(0.0, 5.0),
+            WallKind::Reflective,
+        )];
+        let from = cgmath::point2(-0.5, 0.0);
+        let mut particles: ParticleStore = vec![moving(from, 1.0, cgmath::vec2(1.0, 0.5))]
+            .into_iter()
+            .collect();
+        assert_eq!(collide(&mut walls, &mut particles, &[from], &mut rng()), 0);
+        let p = particles.get(0);
+        assert!((p.position() - cgmath::point2(-0.5, 0.5)).magnitude() < 1e-12);
+        assert!((p.velocity() - cgmath::vec2(-1.0, 0.5)).magnitude() < 1e-12);
+        assert!((walls[0].momentum_transferred() - cgmath::vec2(4.0, 0.0)).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn misses_past_the_end_of_a_segment() {
+        let mut walls = vec![Wall::new(
+            cgmath::point2(0.0, -1.0),
+            cgmath::point2(0.0, 1.0),
+            WallKind::Reflective,
+        )];
+        let from = cgmath::point2(-0.5, 2.0);
+        let mut particles: ParticleStore = vec![moving(from, 1.0, cgmath::vec2(1.0, 0.0))]
+            .into_iter()
+            .collect();
+        collide(&mut walls, &mut particles, &[from], &mut rng());
+        assert_eq!(particles.get(0).velocity(), cgmath::vec2(1.0, 0.0));
+    }
+
+    #[test]
+    fn bounces_out_of_a_corner() {
+        let mut walls = Wall::rectangle(
+            cgmath::point2(-1.0, -1.0),
+            cgmath::point2(1.0, 1.0),
+            WallKind::Reflective,
+        );
+        let from = cgmath::point2(0.8, 0.9);
+        let mut particles: ParticleStore = vec![moving(from, 1.0, cgmath::vec2(0.5, 0.3))]
+            .into_iter()
+            .collect();
+        collide(&mut walls, &mut particles, &[from], &mut rng());
+        let p = particles.get(0);
+        assert!((p.position() - cgmath::point2(0.7, 0.8)).magnitude() < 1e-12);
+        assert!((p.velocity() - cgmath::vec2(-0.5, -0.3)).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn absorbs_particles() {
+        let mut walls = vec![Wall::new(
+            cgmath::point2(0.0, -5.0),
+            cgmath::point2(0.0, 5.0),
+            WallKind::Absorbing,
+        )];
+        let from = [cgmath::point2(-0.5, 0.0), cgmath::point2(-2.0, 1.0)];
+        let mut particles: ParticleStore = vec![
+            moving(from[0], 1.0, cgmath::vec2(1.0, 0.0)),
+            moving(from[1], 1.0, cgmath::vec2(1.0, 0.0)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(collide(&mut walls, &mut particles, &from, &mut rng()), 1);
+        assert_eq!(particles.len(), 1);
+        assert_eq!(particles.get(0).position(), cgmath::point2(-1.0, 1.0));
+        assert_eq!(walls[0].momentum_transferred(), cgmath::vec2(2.0, 0.0));
+    }
+
+    #[test]
+    fn thermal_wall_emits_at_its_temperature() {
+        let temperature = 1.5;
+        let mut walls = vec![Wall::new(
+            cgmath::point2(0.0, -1e6),
+            cgmath::point2(0.0, 1e6),
+            WallKind::Thermal { temperature },
+        )];
+        let from = vec![cgmath::point2(-0.1, 0.0); 20000];
+        let mut particles: ParticleStore = from
+            .iter()
+            .map(|&p| moving(p, 1.0, cgmath::vec2(0.2, 0.0)))
+            .collect();
+        collide(&mut walls, &mut particles, &from, &mut rng());
+        assert!(particles
+            .iter()
+            .all(|p| p.velocity().x < 0.0 && p.position().x < 0.0));
+        // flux weighted emission gives the normal direction twice the energy of the tangential
+        let n = particles.len() as f64;
+        let mass = particles.get(0).mass();
+        let normal = particles
+            .iter()
+            .map(|p| mass * p.velocity().x.powi(2))
+            .sum::<f64>()
+            / n;
+        let tangential = particles
+            .iter()
+            .map(|p| mass * p.velocity().y.powi(2))
+            .sum::<f64>()
+            / n;
+        assert!(
+            (normal / (2.0 * temperature) - 1.0).abs() < 0.05,
+            "{}",
+            normal
+        );
+        assert!(
+            (tangential / temperature - 1.0).abs() < 0.05,
+            "{}",
+            tangential
+        );
+    }
+
+    #[test]
+    fn pressure_counts_outward_momentum() {
+        let mut walls = Wall::rectangle(
+            cgmath::point2(-1.0, -1.0),
+            cgmath::point2(1.0, 1.0),
+            WallKind::Reflective,
+        );
+        let from = [cgmath::point2(0.9, 0.0), cgmath::point2(-0.9, 0.5)];
+        let mut particles: ParticleStore = vec![
+            moving(from[0], 0.5, cgmath::vec2(1.0, 0.0)),
+            moving(from[1], 0.5, cgmath::vec2(-1.0, 0.0)),
+        ]
+        .into_iter()
+        .collect();
+        collide(&mut walls, &mut particles, &from, &mut rng());
+        // two hits giving 2 m v each, spread over 8 units of wall in 1 unit of time
+        let p = pressure(&walls, cgmath::point2(0.0, 0.0), 1.0);
+        assert!((p - 1.0).abs() < 1e-12, "{}", p);
+    }
+}